@@ -0,0 +1,235 @@
+// Which part of the grid is shown in the window, and how large.
+//
+// Points on the grid are measured in cells, with the bottom left corner of
+// the grid at (0, 0), so the cell in row y and column x covers the square
+// from (x, y) to (x + 1, y + 1). Points in the window are measured in
+// pixels from its top left corner, as winit reports them.
+//
+// Cells are drawn a whole number of pixels wide, with their edges on the
+// edges of pixels, so that they all look the same. When they're smaller
+// than a pixel, a whole number of them share each pixel.
+
+use cgmath::{Matrix4, Point2, Vector2, Vector3};
+
+use crate::dimensions::Dimensions;
+
+// The most pixels a cell can be zoomed in to.
+const MAX_SCALE: f32 = 64.0;
+
+// How far the whole grid can be zoomed out, as a fraction of its size when
+// it just fits in the window.
+const MIN_FIT: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    grid: Dimensions,
+    window: Dimensions,
+    // The point on the grid which is at the middle of the window.
+    center: Point2<f32>,
+    // How many pixels wide each cell is: a whole number, or one over a whole
+    // number.
+    scale: f32,
+}
+
+impl Camera {
+    // A camera which shows the whole grid, as large as it fits, with any
+    // room left over around it.
+    pub fn new(
+        grid: Dimensions,
+        window: Dimensions,
+    ) -> Self {
+        let mut camera = Camera {
+            grid,
+            window,
+            center: Point2::new(0.0, 0.0),
+            scale: 1.0,
+        };
+        camera.reset();
+        camera
+    }
+
+    // Go back to showing the whole grid.
+    pub fn reset(&mut self) {
+        self.center = Point2::new(self.grid.width() as f32 / 2.0,
+            self.grid.height() as f32 / 2.0);
+        self.scale = self.fit_scale();
+    }
+
+    // Follow a change in the window's size, keeping the same point in the
+    // middle of it at the same scale.
+    pub fn resize(
+        &mut self,
+        window: Dimensions,
+    ) {
+        self.window = window;
+        self.scale = self.scale.max(self.min_scale());
+    }
+
+    // Move what's shown by the given number of pixels, as when it's dragged
+    // by the mouse. The middle of the window stays on the grid.
+    pub fn pan(
+        &mut self,
+        pixels: Vector2<f32>,
+    ) {
+        let (w, h) = (self.grid.width() as f32, self.grid.height() as f32);
+        self.center.x = (self.center.x - pixels.x / self.scale).max(0.0).min(w);
+        self.center.y = (self.center.y + pixels.y / self.scale).max(0.0).min(h);
+    }
+
+    // Zoom in by the given factor, or out if it's less than 1, keeping the
+    // point of the grid that's at the given pixel where it is.
+    pub fn zoom(
+        &mut self,
+        factor: f32,
+        pixel: Point2<f32>,
+    ) {
+        let fixed = self.grid_point(pixel);
+        let mut scale = round_scale(self.scale * factor);
+        if scale == self.scale && factor > 1.0 {
+            scale = next_scale(scale);
+        } else if scale == self.scale && factor < 1.0 {
+            scale = prev_scale(scale);
+        }
+        let scale = scale.min(MAX_SCALE).max(self.min_scale());
+        self.center = fixed + (self.center - fixed) * (self.scale / scale);
+        self.scale = scale;
+    }
+
+    // The point on the grid which is shown at a pixel in the window.
+    pub fn grid_point(
+        &self,
+        pixel: Point2<f32>,
+    ) -> Point2<f32> {
+        let origin = self.origin();
+        Point2::new((pixel.x - origin.x) / self.scale, (origin.y - pixel.y) / self.scale)
+    }
+
+    // How many pixels wide each cell is.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // The pixel at the middle of the window.
+    pub fn middle(&self) -> Point2<f32> {
+        Point2::new(self.window.width() as f32 / 2.0, self.window.height() as f32 / 2.0)
+    }
+
+    // The transform from the corners of the renderer's quad, at (-1, -1)
+    // and (1, 1), to OpenGL's clip space, which puts the grid where this
+    // camera shows it.
+    pub fn transform(&self) -> Matrix4<f32> {
+        let (w, h) = (self.grid.width() as f32, self.grid.height() as f32);
+        let quad_to_grid = Matrix4::from_translation(Vector3::new(w / 2.0, h / 2.0, 0.0))
+            * Matrix4::from_nonuniform_scale(w / 2.0, h / 2.0, 1.0);
+
+        let origin = self.origin();
+        let (window_w, window_h) = (self.window.width() as f32, self.window.height() as f32);
+        let grid_to_clip = cgmath::ortho(
+            -origin.x / self.scale, (window_w - origin.x) / self.scale,
+            (origin.y - window_h) / self.scale, origin.y / self.scale,
+            -1.0, 1.0);
+        grid_to_clip * quad_to_grid
+    }
+
+    // The pixel at the bottom left corner of the grid, rounded so that the
+    // edges of cells fall on the edges of pixels.
+    fn origin(&self) -> Point2<f32> {
+        let middle = self.middle();
+        Point2::new((middle.x - self.center.x * self.scale).round(),
+            (middle.y + self.center.y * self.scale).round())
+    }
+
+    // The largest scale at which the whole grid fits in the window.
+    fn fit_scale(&self) -> f32 {
+        floor_scale((self.window.width() as f32 / self.grid.width() as f32)
+            .min(self.window.height() as f32 / self.grid.height() as f32))
+    }
+
+    fn min_scale(&self) -> f32 {
+        floor_scale(self.fit_scale() * MIN_FIT).min(MAX_SCALE)
+    }
+}
+
+// The largest scale that cells can be drawn at which is no larger than the
+// given one.
+fn floor_scale(scale: f32) -> f32 {
+    if scale >= 1.0 {
+        scale.floor()
+    } else {
+        1.0 / (1.0 / scale).ceil()
+    }
+}
+
+// The scale that cells can be drawn at which is nearest to the given one.
+fn round_scale(scale: f32) -> f32 {
+    if scale >= 1.0 {
+        scale.round()
+    } else {
+        1.0 / (1.0 / scale).round()
+    }
+}
+
+// The scales that cells can be drawn at either side of one they can.
+fn next_scale(scale: f32) -> f32 {
+    if scale >= 1.0 {
+        scale + 1.0
+    } else {
+        1.0 / (1.0 / scale - 1.0)
+    }
+}
+
+fn prev_scale(scale: f32) -> f32 {
+    if scale > 1.0 {
+        scale - 1.0
+    } else {
+        1.0 / (1.0 / scale + 1.0)
+    }
+}
+
+#[test]
+fn camera() {
+    use cgmath::Vector4;
+
+    let mut camera = Camera::new(Dimensions::new(100, 50), Dimensions::new(400, 400));
+
+    // The whole grid fits across the window, and its middle is in the
+    // middle of the window.
+    assert_eq!(camera.grid_point(Point2::new(0.0, 200.0)), Point2::new(0.0, 25.0));
+    assert_eq!(camera.grid_point(Point2::new(400.0, 200.0)), Point2::new(100.0, 25.0));
+    let corner = camera.transform() * Vector4::new(1.0, 1.0, 1.0, 1.0);
+    assert_eq!((corner.x, corner.y), (1.0, 0.5));
+
+    // Zooming keeps the point under the cursor where it is.
+    let cursor = Point2::new(100.0, 300.0);
+    let before = camera.grid_point(cursor);
+    camera.zoom(8.0, cursor);
+    let after = camera.grid_point(cursor);
+    assert_eq!(camera.scale, 32.0);
+    assert!((before - after).x.abs() < 1.0 / 32.0 && (before - after).y.abs() < 1.0 / 32.0);
+    camera.zoom(1000.0, cursor);
+    assert_eq!(camera.scale, MAX_SCALE);
+    camera.zoom(0.0, cursor);
+    assert_eq!(camera.scale, 1.0);
+
+    // Cells are always a whole number of pixels wide, or share pixels
+    // evenly, however little the zoom is by.
+    camera.zoom(1.1, cursor);
+    assert_eq!(camera.scale, 2.0);
+    camera.zoom(1.0 / 1.1, cursor);
+    camera.zoom(1.0 / 1.1, cursor);
+    assert_eq!(camera.scale, 1.0);
+
+    // A grid which doesn't fill the window is drawn in the middle of it,
+    // with its cells on the edges of pixels.
+    let small = Camera::new(Dimensions::new(64, 48), Dimensions::new(1001, 700));
+    assert_eq!(small.scale, 14.0);
+    assert_eq!(small.origin(), Point2::new(53.0, 686.0));
+
+    // Dragging the grid right shows more of its left side, and up shows
+    // more of its bottom; it can't be dragged away entirely.
+    camera.reset();
+    camera.pan(Vector2::new(40.0, -40.0));
+    assert_eq!(camera.grid_point(camera.middle()), Point2::new(40.0, 15.0));
+    camera.pan(Vector2::new(1000.0, 0.0));
+    assert_eq!(camera.grid_point(camera.middle()), Point2::new(0.0, 15.0));
+}
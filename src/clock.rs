@@ -0,0 +1,173 @@
+// How fast the window's simulation runs, whatever rate it's redrawn at:
+// how many steps it takes each second, and whether it's paused. Each frame
+// takes as many steps as are due in the time since the last one, so the
+// simulation runs at the same speed at 30 frames a second as at 144. A step
+// is a generation, except with Hashlife, where it's as many generations as
+// that was asked to step by.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+// The speeds to choose from, slowest first, in steps per second.
+const SPEEDS: &[u64] = &[
+    1, 2, 4, 8, 15, 30, 60, 120, 240, 480, 960, 1920, 3840, 7680, 15360,
+];
+
+// 60 steps a second, which is one per frame at the usual frame rate.
+const DEFAULT_SPEED: usize = 6;
+
+// The usual frame rate. The first frame has no earlier one to count the
+// time from, so it takes as many steps as a frame at this rate does.
+const FRAMES_PER_SECOND: u128 = 60;
+
+// The most time that's made up for in one frame, so that the grid doesn't
+// leap ahead after the window stops being redrawn for a while.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+// Comma and period go by as many steps as are due in this fraction of a
+// second.
+const JUMPS_PER_SECOND: u64 = 60;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+pub struct Clock {
+    // The speed, as an index into SPEEDS.
+    speed: usize,
+    paused: bool,
+    // When the clock last ticked, and the part of a step which was due by
+    // then but not taken, in steps times nanoseconds per second, so that
+    // no time is lost to rounding.
+    last_tick: Option<Instant>,
+    owed: u128,
+    // Steps asked for one at a time, which are taken even when paused.
+    single_steps: u32,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            speed: DEFAULT_SPEED,
+            paused: false,
+            last_tick: None,
+            owed: 0,
+            single_steps: 0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    // Pause, and take the given number of steps more in the next frame.
+    pub fn single_step(
+        &mut self,
+        steps: u32,
+    ) {
+        self.paused = true;
+        self.single_steps += steps;
+    }
+
+    // How many steps are due in a 60th of a second at this speed, or one if
+    // that's fewer.
+    pub fn jump_steps(&self) -> u32 {
+        (SPEEDS[self.speed] / JUMPS_PER_SECOND).max(1) as u32
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    // Go on to a frame drawn at the given time, and say how many steps to
+    // take in it.
+    pub fn tick(
+        &mut self,
+        now: Instant,
+    ) -> u32 {
+        let speed = SPEEDS[self.speed];
+        let due = match self.last_tick {
+            Some(last) => steps_due(now.saturating_duration_since(last).min(MAX_CATCH_UP), speed),
+            None => steps_due(Duration::from_secs(1), speed) / FRAMES_PER_SECOND,
+        };
+        self.last_tick = Some(now);
+
+        let mut steps = self.single_steps;
+        self.single_steps = 0;
+        if !self.paused {
+            self.owed += due;
+            steps += (self.owed / NANOS_PER_SECOND) as u32;
+            self.owed %= NANOS_PER_SECOND;
+        }
+        steps
+    }
+}
+
+// How many steps are due in the given time at the given speed, times
+// nanoseconds per second.
+fn steps_due(
+    time: Duration,
+    speed: u64,
+) -> u128 {
+    time.as_nanos() * u128::from(speed)
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SPEEDS[self.speed] {
+            1 => write!(f, "1 step per second")?,
+            steps => write!(f, "{} steps per second", steps)?,
+        }
+        if self.paused {
+            write!(f, ", paused")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn clock() {
+    let start = Instant::now();
+    let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+    let mut clock = Clock::new();
+    assert_eq!(clock.tick(start), 1);
+
+    // A second's worth of frames takes as many steps at any frame rate.
+    let second = |clock: &mut Clock, from: f64, fps: u32| -> u32 {
+        (1..=fps).map(|i| clock.tick(at(from + f64::from(i) / f64::from(fps)))).sum()
+    };
+    assert_eq!(second(&mut clock, 0.0, 30), 60);
+    assert_eq!(second(&mut clock, 1.0, 144), 60);
+
+    for _ in 0..3 {
+        clock.slower();
+    }
+    assert_eq!(clock.to_string(), "8 steps per second");
+    assert_eq!(second(&mut clock, 2.0, 60), 8);
+    assert_eq!(clock.jump_steps(), 1);
+
+    // A long wait between frames is only made up for in part.
+    assert_eq!(clock.tick(at(13.0)), 2);
+
+    // Single steps are taken at once, and pause the clock.
+    clock.single_step(1);
+    clock.single_step(1);
+    assert_eq!(clock.tick(at(13.1)), 2);
+    assert_eq!(second(&mut clock, 13.1, 60), 0);
+    assert_eq!(clock.to_string(), "8 steps per second, paused");
+
+    clock.toggle_pause();
+    for _ in 0..100 {
+        clock.faster();
+    }
+    assert_eq!(clock.tick(at(14.2)), 1536);
+    assert_eq!(clock.jump_steps(), 256);
+}
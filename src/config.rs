@@ -0,0 +1,399 @@
+// Settings for a run, taken from the command line and an optional config
+// file. Each option can be given on the command line as "--name value", or
+// in a config file as a "name = value" line; options on the command line
+// override those in the file. Lines in the file starting with "#" are
+// comments.
+//
+//   size      the size of the grid, as WIDTHxHEIGHT (default: the window size
+//             divided by the cell size, or the size given by the topology)
+//   cell      how many pixels wide each cell is, when the grid's size comes
+//             from the window's (default: 1)
+//   resize    what happens to a grid which takes its size from the window
+//             when the window is resized: "view" to keep the grid and fit
+//             the view of it to the window, or "center" or "corner" to grow
+//             or shrink the grid with the window, keeping the cells in its
+//             middle or its top left corner where they are (default: view)
+//   topology  the topology of the grid, in Golly's notation (default: T)
+//   rule      the rule (default: the pattern's rule, or B3/S23)
+//   seed      the seed for the random initial soup (default: 42)
+//   density   the fraction of cells alive in the initial soup (default: 0.3)
+//   warmup    how many generations to run before the first frame (default:
+//             100 for a random soup, or 0 when loading a pattern)
+//   pattern   a pattern file to load into the middle of an empty grid,
+//             instead of starting with a random soup
+//   headless  run this many generations without opening a window, then save
+//             the grid to the output file
+//   output    where a headless run saves the grid: a pattern file, in the
+//             format given by its extension, or a ".pgm" image (default:
+//             life-N.rle, for N generations)
+//   adapter   the name, or part of the name, of the GPU adapter to use for
+//             a headless run, such as "llvmpipe" for a software renderer
+//   packed    "true" to store cells as single bits, which is faster but
+//             only works for two-state rules like B3/S23; on the command
+//             line, just "--packed"
+//   kernel    "tiled" to load each workgroup's cells into workgroup memory
+//             before stepping them, "blocked" to also step several
+//             generations at a time in workgroup memory when running many
+//             generations, or "direct" to read cells from the cell buffer as
+//             needed (default: blocked)
+//   bench     time this many generations with each kernel and with packed
+//             cells, without opening a window
+//   hashlife  step the grid on the CPU with Hashlife, 2^K generations at a
+//             time, as an unbounded plane rather than with the topology;
+//             only for two-state rules like B3/S23
+//   unbounded "true" to run on an unbounded plane, held as tiles of cells
+//             which are only kept where there's anything alive, rather than
+//             with the topology; only for Life-like rules of radius 1
+//             without B0; on the command line, just "--unbounded"
+//   tiles     how many tiles an unbounded plane can hold (default: 1024)
+//   history   how many MiB of GPU memory to keep earlier generations in, for
+//             winding the grid back (default: 256, at most 1048576)
+//   verify    "true" to check the result of a headless run against the CPU
+//             reference implementation; on the command line, just "--verify"
+
+use std::{fs, path::PathBuf, str::FromStr};
+
+use crate::{
+    dimensions::Dimensions,
+    hashlife::MAX_STEP_LOG,
+    life::Kernel,
+    rule::Rule,
+    topology::Topology,
+};
+
+pub const USAGE: &str = "\
+usage: life [options]
+    --config FILE       read options from FILE, as \"name = value\" lines
+    --size WxH          size of the grid (default: the window size)
+    --cell N            pixels per cell, for a grid the size of the window
+    --resize R          view, center or corner (default: view)
+    --topology T        topology of the grid, e.g. T, P100,80 or K100*,80
+    --rule RULE         rule, e.g. B3/S23 or R5,C0,M1,S34..58,B34..45,NM
+    --seed N            seed for the random initial soup (default: 42)
+    --density D         fraction of live cells in the soup (default: 0.3)
+    --warmup N          generations to run before the first frame
+    --pattern FILE      load a pattern instead of a random soup
+    --headless N        run N generations without a window, then save
+    --output FILE       where to save a headless run (.rle, .cells, .pgm, ...)
+    --adapter NAME      which GPU adapter to use for a headless run
+    --packed            store cells as bits, for speed with rules like B3/S23
+    --kernel K          direct, tiled or blocked (default: blocked)
+    --bench N           time N generations with each kernel, without a window
+    --hashlife K        step 2^K generations at a time with Hashlife on the CPU
+    --unbounded         run on an unbounded plane of tiles, for rules like B3/S23
+    --tiles N           how many 64x64 tiles that plane can hold (default: 1024)
+    --history N         MiB of GPU memory for winding back (default: 256)
+    --verify            check a headless run against the CPU implementation";
+
+// The most MiB of GPU memory the history can be given: a TiB, far more
+// than any GPU has, and well short of what would overflow when it's
+// counted in bytes.
+const MAX_HISTORY: u64 = 1 << 20;
+
+// Options which take no value on the command line.
+const FLAGS: &[&str] = &["packed", "unbounded", "verify"];
+
+// What happens to a grid which takes its size from the window when the
+// window is resized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resize {
+    // The grid stays as it is, and only the view of it changes.
+    View,
+    // The grid grows or shrinks to fit the window, keeping the cells in its
+    // middle, or in its top left corner, where they are.
+    Center,
+    Corner,
+}
+
+impl Resize {
+    // Where the top left cell of a grid of one size goes in a grid of
+    // another, when it's resized this way; it may be outside the new grid.
+    pub fn offset(
+        &self,
+        from: Dimensions,
+        to: Dimensions,
+    ) -> (i64, i64) {
+        match self {
+            Resize::View | Resize::Corner => (0, 0),
+            Resize::Center => (
+                (i64::from(to.width()) - i64::from(from.width())) / 2,
+                (i64::from(to.height()) - i64::from(from.height())) / 2,
+            ),
+        }
+    }
+}
+
+impl FromStr for Resize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "view" => Ok(Resize::View),
+            "center" => Ok(Resize::Center),
+            "corner" => Ok(Resize::Corner),
+            _ => Err(format!("resize \"{}\" should be view, center or corner", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub size: Option<Dimensions>,
+    pub cell: u32,
+    pub resize: Resize,
+    pub topology: Topology,
+    pub rule: Option<Rule>,
+    pub seed: u64,
+    pub density: f32,
+    pub warmup: Option<u32>,
+    pub pattern: Option<PathBuf>,
+    pub headless: Option<u64>,
+    pub output: Option<PathBuf>,
+    pub adapter: Option<String>,
+    pub packed: bool,
+    pub kernel: Kernel,
+    pub bench: Option<u32>,
+    pub hashlife: Option<u32>,
+    pub unbounded: bool,
+    pub tiles: u32,
+    pub history: u64,
+    pub verify: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            size: None,
+            cell: 1,
+            resize: Resize::View,
+            topology: Topology::torus(),
+            rule: None,
+            seed: 42,
+            density: 0.3,
+            warmup: None,
+            pattern: None,
+            headless: None,
+            output: None,
+            adapter: None,
+            packed: false,
+            kernel: Kernel::Blocked,
+            bench: None,
+            hashlife: None,
+            unbounded: false,
+            tiles: 1024,
+            history: 256,
+            verify: false,
+        }
+    }
+}
+
+impl Config {
+    // Parse the command-line arguments, not including the program name.
+    // A config file named by "--config" is read first, wherever it appears.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.to_string(),
+                None => return Err(format!("unexpected argument \"{}\"", arg)),
+            };
+            if name == "help" {
+                return Err(USAGE.to_string());
+            }
+            let value = if FLAGS.contains(&name.as_str()) {
+                "true".to_string()
+            } else {
+                args.next().ok_or_else(|| format!("--{} needs a value", name))?
+            };
+            options.push((name, value));
+        }
+
+        let mut config = Config::default();
+        for (_, file) in options.iter().filter(|(name, _)| name == "config") {
+            let text = fs::read_to_string(file).map_err(|e|
+                format!("couldn't read {}: {}", file, e))?;
+            config.read_file(&text).map_err(|e| format!("{}: {}", file, e))?;
+        }
+        for (name, value) in options.iter().filter(|(name, _)| name != "config") {
+            config.set(name, value).map_err(|e| format!("--{}: {}", name, e))?;
+        }
+        if config.hashlife.is_some() && config.unbounded {
+            return Err("--hashlife and --unbounded can't be used together".to_string());
+        }
+        Ok(config)
+    }
+
+    // Apply the settings in the text of a config file.
+    pub fn read_file(
+        &mut self,
+        text: &str,
+    ) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once('=').ok_or_else(||
+                format!("line {}: expected \"name = value\"", i + 1))?;
+            self.set(name.trim(), value.trim()).map_err(|e|
+                format!("line {}: {}", i + 1, e))?;
+        }
+        Ok(())
+    }
+
+    fn set(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<(), String> {
+        let invalid = || format!("invalid {} \"{}\"", name, value);
+        match name {
+            "size" => {
+                let (width, height) = value.split_once(|c| c == 'x' || c == 'X')
+                    .ok_or_else(invalid)?;
+                let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+                let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+                if width == 0 || height == 0 {
+                    return Err(invalid());
+                }
+                self.size = Some(Dimensions::new(width, height));
+            }
+            "cell" => {
+                self.cell = value.parse().map_err(|_| invalid())?;
+                if self.cell == 0 {
+                    return Err(invalid());
+                }
+            }
+            "resize" => self.resize = value.parse()?,
+            "topology" => self.topology = Topology::parse(value)?,
+            "rule" => self.rule = Some(Rule::parse(value)?),
+            "seed" => self.seed = value.parse().map_err(|_| invalid())?,
+            "density" => {
+                let density: f32 = value.parse().map_err(|_| invalid())?;
+                if !(0.0..=1.0).contains(&density) {
+                    return Err(format!("density {} should be between 0 and 1", density));
+                }
+                self.density = density;
+            }
+            "warmup" => self.warmup = Some(value.parse().map_err(|_| invalid())?),
+            "pattern" => self.pattern = Some(PathBuf::from(value)),
+            "headless" => self.headless = Some(value.parse().map_err(|_| invalid())?),
+            "output" => self.output = Some(PathBuf::from(value)),
+            "adapter" => self.adapter = Some(value.to_string()),
+            "packed" => self.packed = value.parse().map_err(|_| invalid())?,
+            "kernel" => self.kernel = value.parse()?,
+            "bench" => self.bench = Some(value.parse().map_err(|_| invalid())?),
+            "hashlife" => {
+                let step: u32 = value.parse().map_err(|_| invalid())?;
+                if step > MAX_STEP_LOG {
+                    return Err(format!("hashlife step {} should be at most {}", step, MAX_STEP_LOG));
+                }
+                self.hashlife = Some(step);
+            }
+            "unbounded" => self.unbounded = value.parse().map_err(|_| invalid())?,
+            "tiles" => {
+                self.tiles = value.parse().map_err(|_| invalid())?;
+                if self.tiles == 0 || self.tiles > 65535 {
+                    return Err(format!("tiles {} should be between 1 and 65535", self.tiles));
+                }
+            }
+            "history" => {
+                self.history = value.parse().map_err(|_| invalid())?;
+                if self.history > MAX_HISTORY {
+                    return Err(format!("history {} should be at most {} MiB",
+                        self.history, MAX_HISTORY));
+                }
+            }
+            "verify" => self.verify = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown option \"{}\"", name)),
+        }
+        Ok(())
+    }
+
+    // The size of the grid, given the size of the window if there is one.
+    // An explicit size must agree with the size given by the topology, if it
+    // has one. Otherwise, the grid fills the window with cells of the size
+    // given.
+    pub fn dimensions(
+        &self,
+        window: Option<Dimensions>,
+    ) -> Result<Dimensions, String> {
+        match (self.size, self.topology.size()) {
+            (Some(size), Some(topology)) if size != topology => Err(format!(
+                "size {}x{} doesn't match topology {}",
+                size.width(), size.height(), self.topology)),
+            (Some(size), _) | (None, Some(size)) => Ok(size),
+            (None, None) => window
+                .map(|window| Dimensions::new((window.width() / self.cell).max(1),
+                    (window.height() / self.cell).max(1)))
+                .ok_or_else(||
+                    "without a window, the grid needs a size or a topology with a size".to_string()),
+        }
+    }
+
+    // Whether the grid takes its size from the window.
+    pub fn sized_by_window(&self) -> bool {
+        self.size.is_none() && self.topology.size().is_none()
+    }
+
+    // How many generations to run before the first frame.
+    pub fn warmup(&self) -> u32 {
+        self.warmup.unwrap_or(if self.pattern.is_some() { 0 } else { 100 })
+    }
+}
+
+#[test]
+fn parse_config() {
+    let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+    assert_eq!(Config::from_args(args("")).unwrap(), Config::default());
+    let config = Config::from_args(args("--size 640x480 --seed 7 --rule B36/S23")).unwrap();
+    assert_eq!(config.size, Some(Dimensions::new(640, 480)));
+    assert_eq!(config.seed, 7);
+    assert_eq!(config.rule, Some(Rule::parse("B36/S23").unwrap()));
+    assert_eq!(config.warmup(), 100);
+    let config = Config::from_args(args("--size 64X48")).unwrap();
+    assert_eq!(config.size, Some(Dimensions::new(64, 48)));
+    assert!(Config::from_args(args("--size 64x")).is_err());
+
+    let mut config = Config::default();
+    config.read_file("# a comment\ndensity = 0.5\n\npattern = gun.rle\n").unwrap();
+    assert_eq!(config.density, 0.5);
+    assert_eq!(config.warmup(), 0);
+    assert!(config.read_file("density = 2").is_err());
+    assert_eq!(config.read_file("colour = red"), Err("line 1: unknown option \"colour\"".to_string()));
+    assert!(Config::from_args(args("--colour red")).is_err());
+    assert!(config.read_file("history = 99999999999").is_err());
+    assert!(config.read_file("history = -1").is_err());
+
+    let config = Config::from_args(args("--topology P100,80")).unwrap();
+    assert_eq!(config.dimensions(None), Ok(Dimensions::new(100, 80)));
+    let config = Config::from_args(args("--topology P100,80 --size 50x50")).unwrap();
+    assert!(config.dimensions(Some(Dimensions::new(1024, 768))).is_err());
+    let config = Config::from_args(args("--cell 8")).unwrap();
+    assert_eq!(config.dimensions(Some(Dimensions::new(1024, 770))), Ok(Dimensions::new(128, 96)));
+    assert!(Config::from_args(args("--cell 0")).is_err());
+    let config = Config::from_args(args("--resize center")).unwrap();
+    assert!(config.sized_by_window());
+    assert_eq!(config.resize.offset(Dimensions::new(100, 80), Dimensions::new(51, 90)), (-24, 5));
+    assert!(Config::from_args(args("--resize stretch")).is_err());
+    let config = Config::from_args(args("--headless 1000 --verify --output out.pgm --packed")).unwrap();
+    assert!(config.packed);
+    assert_eq!(config.headless, Some(1000));
+    assert!(config.verify);
+    assert!(config.dimensions(None).is_err());
+    assert_eq!(Config::from_args(args("--kernel direct")).unwrap().kernel, Kernel::Direct);
+    assert!(Config::from_args(args("--kernel fast")).is_err());
+    let config = Config::from_args(args("--headless 10000000000 --hashlife 20")).unwrap();
+    assert_eq!((config.headless, config.hashlife), (Some(10_000_000_000), Some(20)));
+    assert!(Config::from_args(args("--hashlife 64")).is_err());
+    let config = Config::from_args(args("--unbounded --tiles 4096")).unwrap();
+    assert!(config.unbounded);
+    assert_eq!(config.tiles, 4096);
+    assert_eq!(Config::from_args(args("--history 64")).unwrap().history, 64);
+    assert!(Config::from_args(args("--history 1048577")).is_err());
+    assert!(Config::from_args(args("--unbounded --hashlife 3")).is_err());
+    assert!(Config::from_args(args("--seed")).is_err());
+    assert!(Config::from_args(args("stray")).is_err());
+}
@@ -0,0 +1,409 @@
+// A reference implementation of life.wgsl which runs on the CPU.
+//
+// Each step computes exactly what the compute shader does, in the same order
+// and with the same parameters, so for Life-like rules the two produce
+// bit-for-bit identical grids. Lenia rules differ slightly, since the GPU's
+// exp() and length() are only approximate. This serves as an oracle for the
+// GPU's results, and as a fallback where there is no usable GPU; it's a
+// LifeGrid like Life, so it can stand in for it.
+
+use crate::{
+    dimensions::Dimensions,
+    life::LifeGrid,
+    life_params::Params,
+};
+
+// How far a Lenia cell may differ from the GPU's value and still be counted
+// as agreeing with it.
+const LENIA_TOLERANCE: f32 = 1e-3;
+
+pub struct CpuLife {
+    params: Params,
+    dimensions: Dimensions,
+    cells: Vec<f32>,
+    next: Vec<f32>,
+    row_counts: Vec<u32>,
+    frame_num: usize,
+}
+
+impl CpuLife {
+    pub fn new(
+        dimensions: Dimensions,
+        params: &Params,
+    ) -> Self {
+        assert_eq!((params.width, params.height), (dimensions.width(), dimensions.height()));
+        CpuLife {
+            params: *params,
+            dimensions,
+            cells: vec![0.0; dimensions.area()],
+            next: vec![0.0; dimensions.area()],
+            row_counts: vec![0; dimensions.area()],
+            frame_num: 0,
+        }
+    }
+
+    pub fn step(&mut self) {
+        let (w, h) = (self.params.width as i32, self.params.height as i32);
+
+        if self.use_row_counts() {
+            let r = self.params.radius as i32;
+            for y in 0..h {
+                for x in 0..w {
+                    self.row_counts[(y * w + x) as usize] =
+                        (-r..=r).map(|dx| self.alive(x + dx, y)).sum();
+                }
+            }
+        }
+
+        let mut next = std::mem::take(&mut self.next);
+        for y in 0..h {
+            for x in 0..w {
+                next[(y * w + x) as usize] = if self.params.lenia != 0 {
+                    self.lenia(x, y)
+                } else {
+                    self.life_like(x, y)
+                };
+            }
+        }
+        self.next = std::mem::replace(&mut self.cells, next);
+        self.frame_num += 1;
+    }
+
+    // Check a grid computed elsewhere, such as on the GPU, against this one.
+    pub fn verify(
+        &self,
+        grid: &[f32],
+    ) -> Result<(), String> {
+        assert_eq!(grid.len(), self.cells.len());
+        let tolerance = if self.params.lenia != 0 { LENIA_TOLERANCE } else { 0.0 };
+        let differ = |(_, (&a, &b)): &(usize, (&f32, &f32))| (a - b).abs() > tolerance;
+
+        let first = match self.cells.iter().zip(grid).enumerate().find(differ) {
+            Some((i, _)) => i,
+            None => return Ok(()),
+        };
+        let count = self.cells.iter().zip(grid).enumerate().filter(differ).count();
+        let width = self.dimensions.width() as usize;
+        Err(format!(
+            "{} of {} cells differ from the CPU reference after {} generations; \
+             the first is at ({}, {}), which is {} but should be {}",
+            count, grid.len(), self.frame_num, first % width, first / width,
+            grid[first], self.cells[first]))
+    }
+
+    // The rest of this mirrors the functions of the same names in life.wgsl.
+
+    fn locate(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> Option<usize> {
+        let p = &self.params;
+        let (w, h) = (p.width as i32, p.height as i32);
+        let (mut cx, mut cy) = (x, y);
+
+        if p.boundary == 1 {
+            if cx < 0 || cx >= w || cy < 0 || cy >= h {
+                return None;
+            }
+            return Some((cy * w + cx) as usize);
+        }
+
+        // Mirrored edges repeat the grid and its reflection, so fold over
+        // twice the size first; a radius may reach further than one reflection.
+        if p.boundary == 2 {
+            cx = wrap(cx, 2 * w);
+            if cx >= w {
+                cx = 2 * w - 1 - cx;
+            }
+            cy = wrap(cy, 2 * h);
+            if cy >= h {
+                cy = 2 * h - 1 - cy;
+            }
+            return Some((cy * w + cx) as usize);
+        }
+
+        if cy < 0 || cy >= h {
+            if p.flip_x != 0 {
+                cx = w - 1 - cx;
+            }
+            if cy < 0 {
+                cx -= p.shift_x;
+            } else {
+                cx += p.shift_x;
+            }
+            cy = wrap(cy, h);
+        }
+        if cx < 0 || cx >= w {
+            if p.flip_y != 0 {
+                cy = h - 1 - cy;
+            }
+            if cx < 0 {
+                cy -= p.shift_y;
+            } else {
+                cy += p.shift_y;
+            }
+            cx = wrap(cx, w);
+            cy = wrap(cy, h);
+        }
+        Some((cy * w + cx) as usize)
+    }
+
+    fn cell(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> f32 {
+        self.locate(x, y).map_or(0.0, |pix| self.cells[pix])
+    }
+
+    fn alive(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> u32 {
+        (self.cell(x, y) > self.params.threshold) as u32
+    }
+
+    fn use_row_counts(&self) -> bool {
+        self.params.lenia == 0 && self.params.radius > 1 && self.params.von_neumann == 0
+    }
+
+    fn row_count(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> u32 {
+        self.locate(x, y).map_or(0, |pix| self.row_counts[pix])
+    }
+
+    fn neighbors(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> u32 {
+        let r = self.params.radius as i32;
+        let mut count = 0;
+
+        if self.use_row_counts() {
+            for dy in -r..=r {
+                count += self.row_count(x, y + dy);
+            }
+        } else {
+            for dy in -r..=r {
+                let reach = if self.params.von_neumann != 0 { r - dy.abs() } else { r };
+                for dx in -reach..=reach {
+                    count += self.alive(x + dx, y + dy);
+                }
+            }
+        }
+
+        if self.params.include_center == 0 {
+            count -= self.alive(x, y);
+        }
+        count
+    }
+
+    fn life_like(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> f32 {
+        let p = &self.params;
+        let thresh = p.threshold;
+        let count = self.neighbors(x, y);
+        let ov = self.cell(x, y);
+
+        if ov < 0.0 {
+            (ov + 1.0).min(0.0)
+        } else if ov > thresh {
+            if in_set(count, p.survival, p.survival_min, p.survival_max) {
+                if ov - p.decay > thresh {
+                    ov - p.decay
+                } else {
+                    ov
+                }
+            } else {
+                -((p.states - 2) as f32)
+            }
+        } else if in_set(count, p.birth, p.birth_min, p.birth_max) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn growth(
+        &self,
+        u: f32,
+    ) -> f32 {
+        let d = (u - self.params.mu) / self.params.sigma;
+        2.0 * (-0.5 * d * d).exp() - 1.0
+    }
+
+    fn lenia(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> f32 {
+        let r = self.params.radius as i32;
+        let mut sum = 0.0;
+        let mut total = 0.0;
+
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let k = kernel(((dx * dx + dy * dy) as f32).sqrt() / r as f32);
+                if k > 0.0 {
+                    sum += k * self.cell(x + dx, y + dy);
+                    total += k;
+                }
+            }
+        }
+
+        (self.cell(x, y) + self.params.dt * self.growth(sum / total)).clamp(0.0, 1.0)
+    }
+}
+
+impl LifeGrid<()> for CpuLife {
+    fn import(
+        &mut self,
+        _: &(),
+        cells: Vec<f32>,
+    ) {
+        assert_eq!(cells.len(), self.dimensions.area());
+        self.cells = cells;
+    }
+
+    fn export(
+        &self,
+        _: &(),
+    ) -> Vec<f32> {
+        self.cells.clone()
+    }
+
+    fn step_n(
+        &mut self,
+        _: &(),
+        generations: u32,
+    ) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        if (params.width, params.height) != (self.params.width, self.params.height) {
+            return Err("the parameters are for a grid of another size".to_string());
+        }
+        self.params = *params;
+        Ok(())
+    }
+
+    fn frame_num(&self) -> usize {
+        self.frame_num
+    }
+}
+
+fn wrap(
+    v: i32,
+    size: i32,
+) -> i32 {
+    ((v % size) + size) % size
+}
+
+pub fn in_set(
+    count: u32,
+    mask: u32,
+    lo: u32,
+    hi: u32,
+) -> bool {
+    if count < 32 && mask & (1 << count) != 0 {
+        return true;
+    }
+    count >= lo && count <= hi
+}
+
+fn kernel(r: f32) -> f32 {
+    if r <= 0.0 || r >= 1.0 {
+        return 0.0;
+    }
+    (4.0 - 1.0 / (r * (1.0 - r))).exp()
+}
+
+#[test]
+fn step_patterns() {
+    use crate::{rule::Rule, topology::Topology};
+
+    let dim = Dimensions::new(8, 8);
+    let grid = |cells: &[(u32, u32)]| {
+        let mut grid = vec![0.0; dim.area()];
+        for &(x, y) in cells {
+            grid[(y * dim.width() + x) as usize] = 1.0;
+        }
+        grid
+    };
+    let live = |life: &CpuLife| {
+        let mut live: Vec<(u32, u32)> = life.export(&()).iter().enumerate()
+            .filter(|(_, &v)| v > 0.7)
+            .map(|(i, _)| ((i % 8) as u32, (i / 8) as u32))
+            .collect();
+        live.sort_by_key(|&(x, y)| (y, x));
+        live
+    };
+    let new = |rule: &str, topology: &str| {
+        let params = Params::new(dim, 0.7, 0.0, &Rule::parse(rule).unwrap(),
+            &Topology::parse(topology).unwrap());
+        CpuLife::new(dim, &params)
+    };
+
+    // A blinker across the left and right edges of a torus.
+    let mut life = new("B3/S23", "T");
+    life.import(&(), grid(&[(7, 3), (0, 3), (1, 3)]));
+    life.step();
+    assert_eq!(live(&life), &[(0, 2), (0, 3), (0, 4)]);
+    life.step();
+    assert_eq!(live(&life), &[(0, 3), (1, 3), (7, 3)]);
+
+    // The same blinker on a bounded plane loses its outer cells.
+    let mut life = new("B3/S23", "P8,8");
+    life.import(&(), grid(&[(7, 3), (0, 3), (1, 3)]));
+    life.step();
+    assert!(live(&life).is_empty());
+
+    // A glider returns to where it started after 32 generations on a torus.
+    let glider = grid(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    let mut life = new("B3/S23", "T");
+    life.import(&(), glider.clone());
+    life.run(&(), 32);
+    assert!(life.verify(&glider).is_ok());
+    assert_eq!(life.frame_num(), 32);
+
+    // Under HighLife, the same cells go on as a glider, but parameters for
+    // a grid of another size can't be used.
+    let highlife = Params::new(dim, 0.7, 0.0, &Rule::parse("B36/S23").unwrap(),
+        &Topology::torus());
+    assert!(life.set_params(&highlife).is_ok());
+    life.run(&(), 32);
+    assert!(life.verify(&glider).is_ok());
+    let other = Params::new(Dimensions::new(16, 8), 0.7, 0.0, &Rule::conway(),
+        &Topology::torus());
+    assert!(life.set_params(&other).is_err());
+
+    // Brian's Brain: a cell that dies is refractory for a generation.
+    let mut life = new("B2/S/C3", "T");
+    life.import(&(), grid(&[(3, 3), (4, 3)]));
+    life.step();
+    assert_eq!(life.export(&())[3 * 8 + 3], -1.0);
+    assert_eq!(live(&life), &[(3, 2), (4, 2), (3, 4), (4, 4)]);
+    assert!(life.verify(&grid(&[])).is_err());
+
+    // With mirrored edges, a radius wider than the grid sees its reflections
+    // over and over, so one cell in the corner reaches every other cell.
+    let mut life = new("R10,C0,M1,S1..441,B1..441,NM", "M8,8");
+    life.import(&(), grid(&[(0, 0)]));
+    life.step();
+    assert_eq!(live(&life).len(), 64);
+}
@@ -50,37 +50,67 @@ where
         command_encoder.pop_debug_group();
     }
 
+    // Enqueue a copy of some entries of the given buffer, which holds entries
+    // of the same type, into some entries of this debug buffer. The same
+    // caveat applies here as for enqueue_copyin().
+    pub fn enqueue_copyin_range(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        buf: &wgpu::Buffer,
+        from: usize,
+        to: usize,
+        nentries: usize,
+    ) {
+        let entry_size = mem::size_of::<T>();
+        command_encoder.copy_buffer_to_buffer(
+            buf, (from * entry_size) as u64,
+            &self.buf, (to * entry_size) as u64,
+            (nentries * entry_size) as u64
+        );
+    }
+
     // Note the above caveat about using enqueue_copyin() with display().
     pub fn display(
         &self,
         device: &wgpu::Device,
     ) {
-        // Start a request to map the debug buffer, and wait for it.
-        let buffer_slice = self.buf.slice(..);
-        let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
-        device.poll(wgpu::Maintain::Wait);
-        match block_on(buffer_future) {
+        match self.read(device) {
             Err(e) => {
                 println!("failed to wait for buffer read: {}", e)
             }
-            Ok(_) => {
-                let data : Vec<u8> = buffer_slice.get_mapped_range().to_vec();
-                let result : Vec<T> = bytemuck::cast_slice(&data).to_vec();
+            Ok(result) => {
                 for d in result {
                     println!("{:?}", d);
                 }
-
-                // Current API requires dropping the data before unmapping.
-                drop(data);
-                self.buf.unmap();
             }
         }
     }
 
-    // Copy the given buffer into this debug buffer and display it immediately.
+    // Read back the contents of this debug buffer.
+    // The same caveat applies here as for display().
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+    ) -> Result<Vec<T>, wgpu::BufferAsyncError> {
+        // Start a request to map the debug buffer, and wait for it.
+        let buffer_slice = self.buf.slice(..);
+        let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        block_on(buffer_future)?;
+
+        let data : Vec<u8> = buffer_slice.get_mapped_range().to_vec();
+        let result : Vec<T> = bytemuck::cast_slice(&data).to_vec();
+
+        // Current API requires dropping the data before unmapping.
+        drop(data);
+        self.buf.unmap();
+
+        Ok(result)
+    }
+
+    // Copy the given buffer into this debug buffer right away.
     // This avoids the caveat mentioned above.
-    #[allow(dead_code)]
-    pub fn copyin_and_display(
+    pub fn copyin(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -92,7 +122,17 @@ where
             });
         self.enqueue_copyin(&mut command_encoder, buf);
         queue.submit(Some(command_encoder.finish()));
+    }
 
+    // Copy the given buffer into this debug buffer and display it immediately.
+    #[allow(dead_code)]
+    pub fn copyin_and_display(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buf: &wgpu::Buffer,
+    ) {
+        self.copyin(device, queue, buf);
         self.display(device);
     }
 }
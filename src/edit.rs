@@ -0,0 +1,213 @@
+// Changes made to the grid by hand, such as drawing on it with the mouse,
+// and the log of them which lets them be undone.
+
+use std::collections::VecDeque;
+
+use cgmath::Point2;
+
+use crate::dimensions::Dimensions;
+
+// A change to one cell: its column and row, and its new value.
+pub type CellEdit = ((u32, u32), f32);
+
+// How many changes the log keeps; older ones can't be undone.
+const MAX_CHANGES: usize = 1000;
+
+// A stroke of the mouse across the grid, which sets every cell that it
+// passes over to the same value. Points on the grid are as the Camera
+// gives them, so the cell in row y and column x covers the square from
+// (x, y) to (x + 1, y + 1).
+pub struct Stroke {
+    dimensions: Dimensions,
+    value: f32,
+    // The cell the stroke last reached, which may be off the grid.
+    last: Option<(i64, i64)>,
+}
+
+impl Stroke {
+    pub fn new(
+        dimensions: Dimensions,
+        value: f32,
+    ) -> Self {
+        Stroke {
+            dimensions,
+            value,
+            last: None,
+        }
+    }
+
+    // Carry the stroke on to a point on the grid, in a straight line from
+    // where it last reached, and return the edits to the cells on the way
+    // which are on the grid.
+    pub fn to(
+        &mut self,
+        point: Point2<f32>,
+    ) -> Vec<CellEdit> {
+        let cell = (point.x.floor() as i64, point.y.floor() as i64);
+        let cells = match self.last {
+            Some(last) => line(last, cell),
+            None => vec![cell],
+        };
+        self.last = Some(cell);
+
+        let (w, h) = (i64::from(self.dimensions.width()), i64::from(self.dimensions.height()));
+        cells.into_iter()
+            .filter(|&(x, y)| x >= 0 && x < w && y >= 0 && y < h)
+            .map(|(x, y)| ((x as u32, y as u32), self.value))
+            .collect()
+    }
+}
+
+// A change made by hand, such as a stroke of the mouse: the edits which
+// made it, in order, and the edits which put back the values those cells
+// had just before, in the same order.
+struct Change {
+    edits: Vec<CellEdit>,
+    undo: Vec<CellEdit>,
+}
+
+// Which way to go through the edit log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Revision {
+    Undo,
+    Redo,
+}
+
+// The changes made by hand, which can be undone and redone. This is kept
+// apart from the grid's own history, so a change can be undone while the
+// grid runs on: that puts back the values the changed cells had when they
+// were changed, and leaves every other cell as it is.
+pub struct EditLog {
+    // The changes which can be undone, oldest first, and the ones which
+    // have been undone and can be redone, most recently undone last.
+    done: VecDeque<Change>,
+    undone: Vec<Change>,
+}
+
+impl EditLog {
+    pub fn new() -> Self {
+        EditLog {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // Record some edits made to the grid, and the edits which undo them, as
+    // a new change or as more of the last one. Nothing undone before this
+    // can be redone after it.
+    pub fn record(
+        &mut self,
+        edits: Vec<CellEdit>,
+        undo: Vec<CellEdit>,
+        new_change: bool,
+    ) {
+        self.undone.clear();
+        match self.done.back_mut() {
+            Some(change) if !new_change => {
+                change.edits.extend(edits);
+                change.undo.extend(undo);
+            }
+            _ => {
+                if self.done.len() == MAX_CHANGES {
+                    self.done.pop_front();
+                }
+                self.done.push_back(Change { edits, undo });
+            }
+        }
+    }
+
+    // Undo the last change, or redo the last one undone, and return the
+    // edits which do that, if there's anything to do.
+    pub fn revise(
+        &mut self,
+        revision: Revision,
+    ) -> Option<Vec<CellEdit>> {
+        match revision {
+            Revision::Undo => {
+                let change = self.done.pop_back()?;
+                let edits = change.undo.iter().rev().cloned().collect();
+                self.undone.push(change);
+                Some(edits)
+            }
+            Revision::Redo => {
+                let change = self.undone.pop()?;
+                let edits = change.edits.clone();
+                self.done.push_back(change);
+                Some(edits)
+            }
+        }
+    }
+
+    // Forget every change, once they no longer apply to the grid.
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
+
+// The cells on a line between two cells, not including the first, which
+// the last part of the stroke ended at. Each cell touches the one before it
+// along an edge or at a corner, so this is Bresenham's algorithm.
+fn line(
+    (x0, y0): (i64, i64),
+    (x1, y1): (i64, i64),
+) -> Vec<(i64, i64)> {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+    let mut cells = Vec::new();
+    while (x, y) != (x1, y1) {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+#[test]
+fn strokes() {
+    let mut stroke = Stroke::new(Dimensions::new(8, 8), 1.0);
+    assert_eq!(stroke.to(Point2::new(1.5, 2.5)), vec![((1, 2), 1.0)]);
+    assert_eq!(stroke.to(Point2::new(1.9, 2.1)), vec![]);
+
+    // A fast stroke leaves no gaps, and goes diagonally where it can.
+    let cells: Vec<_> = stroke.to(Point2::new(5.5, 4.5)).into_iter().map(|(c, _)| c).collect();
+    assert_eq!(cells, vec![(2, 3), (3, 3), (4, 4), (5, 4)]);
+
+    // Cells off the grid are left out, but the stroke carries on from them.
+    let mut stroke = Stroke::new(Dimensions::new(8, 8), 0.0);
+    assert_eq!(stroke.to(Point2::new(-2.0, 0.5)), vec![]);
+    assert_eq!(stroke.to(Point2::new(1.5, 0.5)), vec![((0, 0), 0.0), ((1, 0), 0.0)]);
+}
+
+#[test]
+fn edit_log() {
+    let mut log = EditLog::new();
+    assert_eq!(log.revise(Revision::Undo), None);
+
+    // A stroke drawn over two frames, over a cell which had faded to 0.8,
+    // and then another stroke.
+    log.record(vec![((1, 1), 1.0), ((2, 1), 1.0)], vec![((1, 1), 0.0), ((2, 1), 0.8)], true);
+    log.record(vec![((2, 1), 1.0)], vec![((2, 1), 1.0)], false);
+    log.record(vec![((5, 5), 0.0)], vec![((5, 5), 1.0)], true);
+
+    assert_eq!(log.revise(Revision::Undo), Some(vec![((5, 5), 1.0)]));
+    // The cell drawn over twice ends up as it was before the first time.
+    assert_eq!(log.revise(Revision::Undo),
+        Some(vec![((2, 1), 1.0), ((2, 1), 0.8), ((1, 1), 0.0)]));
+    assert_eq!(log.revise(Revision::Undo), None);
+    assert_eq!(log.revise(Revision::Redo),
+        Some(vec![((1, 1), 1.0), ((2, 1), 1.0), ((2, 1), 1.0)]));
+
+    // Once something new is done, what was undone is gone.
+    log.record(vec![((0, 0), 1.0)], vec![((0, 0), 0.0)], true);
+    assert_eq!(log.revise(Revision::Redo), None);
+    assert_eq!(log.revise(Revision::Undo), Some(vec![((0, 0), 0.0)]));
+}
@@ -34,6 +34,9 @@ pub enum ShaderStage {
 }
 
 pub trait Example: 'static + Sized {
+    // Settings passed through run() to init().
+    type Config: Default + 'static;
+
     fn optional_features() -> wgpu::Features {
         wgpu::Features::empty()
     }
@@ -48,6 +51,7 @@ pub trait Example: 'static + Sized {
         adapter: &wgpu::Adapter,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+        config: &Self::Config,
     ) -> Self;
     fn resize(
         &mut self,
@@ -175,6 +179,7 @@ fn start<E: Example>(
         device,
         queue,
     }: Setup,
+    config: E::Config,
 ) {
     let spawner = Spawner::new();
     let mut sc_desc = wgpu::SwapChainDescriptor {
@@ -187,7 +192,7 @@ fn start<E: Example>(
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
     log::info!("Initializing the example...");
-    let mut example = E::init(&sc_desc, &adapter, &device, &queue);
+    let mut example = E::init(&sc_desc, &adapter, &device, &queue, &config);
 
     #[cfg(not(target_arch = "wasm32"))]
     let mut last_update_inst = Instant::now();
@@ -303,6 +308,63 @@ fn start<E: Example>(
     });
 }
 
+// Create a device without a window or surface, for running where there's no
+// display. If an adapter name is given, the first adapter whose name contains
+// it is used, which allows picking a software adapter such as lavapipe;
+// otherwise an adapter is chosen as it is for a window.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn headless<E: Example>(
+    adapter_name: Option<&str>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), String> {
+    // The logger may already be set up, when headless runs are made more
+    // than once in a process, as tests do.
+    let _ = env_logger::try_init();
+
+    let backend = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY);
+    let instance = wgpu::Instance::new(backend);
+    let adapter = match adapter_name {
+        Some(name) => {
+            let name = name.to_lowercase();
+            instance
+                .enumerate_adapters(backend)
+                .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+                .ok_or_else(|| {
+                    let names: Vec<String> = instance
+                        .enumerate_adapters(backend)
+                        .map(|adapter| adapter.get_info().name)
+                        .collect();
+                    format!("no GPU adapter matches \"{}\"; found {:?}", name, names)
+                })?
+        }
+        None => pollster::block_on(
+            wgpu::util::initialize_adapter_from_env_or_default(&instance, backend))
+            .ok_or_else(|| "no suitable GPU adapters found on the system".to_string())?,
+    };
+
+    let adapter_info = adapter.get_info();
+    println!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
+
+    let adapter_features = adapter.features();
+    let required_features = E::required_features();
+    if !adapter_features.contains(required_features) {
+        return Err(format!("adapter does not support required features: {:?}",
+            required_features - adapter_features));
+    }
+
+    let trace_dir = std::env::var("WGPU_TRACE");
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: (E::optional_features() & adapter_features) | required_features,
+            limits: E::required_limits(),
+        },
+        trace_dir.ok().as_ref().map(std::path::Path::new),
+    ))
+    .map_err(|e| format!("couldn't create a device: {}", e))?;
+
+    Ok((adapter, device, queue))
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub struct Spawner<'a> {
     executor: async_executor::LocalExecutor<'a>,
@@ -342,19 +404,19 @@ impl Spawner {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub fn run<E: Example>(title: &str) {
+pub fn run<E: Example>(title: &str, config: E::Config) {
     let setup = pollster::block_on(setup::<E>(title));
-    start::<E>(setup);
+    start::<E>(setup, config);
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn run<E: Example>(title: &str) {
+pub fn run<E: Example>(title: &str, config: E::Config) {
     use wasm_bindgen::{prelude::*, JsCast};
 
     let title = title.to_owned();
     wasm_bindgen_futures::spawn_local(async move {
         let setup = setup::<E>(&title).await;
-        let start_closure = Closure::once_into_js(move || start::<E>(setup));
+        let start_closure = Closure::once_into_js(move || start::<E>(setup, config));
 
         // make sure to handle JS exceptions thrown inside start.
         // Otherwise wasm_bindgen_futures Queue would break and never handle any tasks again.
@@ -442,6 +504,7 @@ pub fn test<E: Example>(mut params: FrameworkRefTest) {
                 &ctx.adapter,
                 &ctx.device,
                 &ctx.queue,
+                &E::Config::default(),
             );
 
             example.render(&dst_view, &ctx.device, &ctx.queue, &spawner);
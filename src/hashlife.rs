@@ -0,0 +1,595 @@
+// A Hashlife engine, which runs on the CPU.
+//
+// The universe is an unbounded plane, held as a quadtree in which any block
+// of cells that appears more than once is stored only once, and in which
+// each block remembers what its middle becomes some number of generations
+// later. Regular patterns, like guns and breeders, are made of a few blocks
+// repeated in space and time, so they can be stepped by billions of
+// generations at once, which a stepper that visits every cell never could.
+// Chaotic patterns gain much less.
+//
+// This only runs two-state rules with the eight-cell Moore neighborhood,
+// like B3/S23, and not those with B0, under which empty space comes alive,
+// since an empty block is taken to stay empty. The topology of the grid is
+// ignored: the cells beyond the edges of the grid that a pattern is imported
+// from are just more of the plane.
+
+use std::collections::HashMap;
+
+use crate::{
+    cpu_life::in_set,
+    dimensions::Dimensions,
+    life_params::Params,
+    texture::Texture,
+};
+
+// Nodes are kept in an arena, and referred to by their index in it.
+type NodeId = u32;
+
+// The nodes at level 0 are single cells; there are just these two.
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+// The level of the smallest root, which is 8x8 cells.
+const MIN_ROOT_LEVEL: u32 = 3;
+
+// The largest power of two that a single step can advance by. This keeps
+// the root's size, and so the positions of its cells, within an i64.
+pub const MAX_STEP_LOG: u32 = 56;
+
+// When there are more nodes than this, the ones no longer in use are
+// thrown away, along with every remembered result.
+const MAX_NODES: usize = 1 << 24;
+
+// A node at level k is a square of 2^k by 2^k cells, made of four nodes
+// at level k - 1.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Node {
+    level: u32,
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+}
+
+pub struct Hashlife {
+    params: Params,
+    dimensions: Dimensions,
+    nodes: Vec<Node>,
+    populations: Vec<u64>,
+    ids: HashMap<Node, NodeId>,
+    // The middle of a node, at half its size, after 2^j generations, keyed
+    // by the node and j.
+    results: HashMap<(NodeId, u32), NodeId>,
+    // The empty node at each level.
+    empty: Vec<NodeId>,
+    root: NodeId,
+    // The position of the root's top left cell, where the grid that was
+    // imported has its top left cell at (0, 0).
+    origin: (i64, i64),
+    generation: u64,
+    // The root and its origin as they were imported, for going back to.
+    start: (NodeId, (i64, i64)),
+}
+
+impl Hashlife {
+    pub fn new(
+        dimensions: Dimensions,
+        params: &Params,
+    ) -> Result<Self, String> {
+        check(params)?;
+
+        let leaf = |id| Node { level: 0, nw: id, ne: id, sw: id, se: id };
+        let mut hashlife = Hashlife {
+            params: *params,
+            dimensions,
+            nodes: vec![leaf(DEAD), leaf(ALIVE)],
+            populations: vec![0, 1],
+            ids: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            origin: (0, 0),
+            generation: 0,
+            start: (DEAD, (0, 0)),
+        };
+        hashlife.root = hashlife.empty(MIN_ROOT_LEVEL);
+        hashlife.start = (hashlife.root, (0, 0));
+        Ok(hashlife)
+    }
+
+    // Step with a new rule from here on. Nothing else in the parameters
+    // matters here.
+    pub fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        check(params)?;
+        let p = &self.params;
+        if (p.birth, p.birth_min, p.birth_max, p.survival, p.survival_min, p.survival_max)
+            != (params.birth, params.birth_min, params.birth_max,
+                params.survival, params.survival_min, params.survival_max) {
+            // What every node becomes depends on the rule.
+            self.results.clear();
+        }
+        self.params = *params;
+        Ok(())
+    }
+
+    // Import some data into the universe, with its top left cell at (0, 0).
+    // Everything outside the grid is dead. This takes the same grid as
+    // Life::import(), and starts again from generation 0.
+    pub fn import(
+        &mut self,
+        cells: Vec<f32>,
+    ) {
+        assert_eq!(cells.len(), self.dimensions.area());
+        let size = self.dimensions.width().max(self.dimensions.height());
+        let level = MIN_ROOT_LEVEL.max(32 - (size - 1).leading_zeros());
+
+        self.root = self.build(&cells, level, 0, 0);
+        self.origin = (0, 0);
+        self.generation = 0;
+        self.start = (self.root, self.origin);
+    }
+
+    // Export the cells where the grid was imported from.
+    pub fn export(&self) -> Vec<f32> {
+        self.viewport(0, 0, self.dimensions)
+    }
+
+    // The cells of the rectangle of the given size whose top left cell is
+    // at (left, top), as 1.0 for live cells and 0.0 for dead ones.
+    pub fn viewport(
+        &self,
+        left: i64,
+        top: i64,
+        dimensions: Dimensions,
+    ) -> Vec<f32> {
+        let mut cells = vec![0.0; dimensions.area()];
+        let view = (left, top, dimensions.width() as i64, dimensions.height() as i64);
+        self.fill(&mut cells, view, self.root, self.origin.0, self.origin.1);
+        cells
+    }
+
+    // Write a viewport into the texture, which must be the size of the grid.
+    pub fn show(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &Texture,
+        left: i64,
+        top: i64,
+    ) {
+        texture.upload(queue, &self.viewport(left, top, self.dimensions));
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // The number of live cells in the whole universe.
+    pub fn population(&self) -> u64 {
+        self.populations[self.root as usize]
+    }
+
+    // The number of live cells at the given generation. Going back in time
+    // means running forward again from the imported grid, but the results
+    // remembered from the first time make that quick.
+    pub fn population_at(
+        &mut self,
+        generation: u64,
+    ) -> u64 {
+        if generation < self.generation {
+            let (root, origin) = self.start;
+            self.root = root;
+            self.origin = origin;
+            self.generation = 0;
+        }
+        self.advance(generation - self.generation);
+        self.population()
+    }
+
+    // Step the given number of generations, as a step for each power of two
+    // in it.
+    pub fn advance(
+        &mut self,
+        generations: u64,
+    ) {
+        for j in (0..64).rev() {
+            if generations & (1 << j) != 0 {
+                let mut remaining = 1u64 << j;
+                while remaining > 1 << MAX_STEP_LOG {
+                    self.step_pow2(MAX_STEP_LOG);
+                    remaining -= 1 << MAX_STEP_LOG;
+                }
+                self.step_pow2(j.min(MAX_STEP_LOG));
+            }
+        }
+    }
+
+    // Step 2^j generations at once.
+    pub fn step_pow2(
+        &mut self,
+        j: u32,
+    ) {
+        assert!(j <= MAX_STEP_LOG);
+        if self.nodes.len() > MAX_NODES {
+            self.collect();
+        }
+
+        // Make the root big enough that the result, which is its middle
+        // half, holds everything the pattern can have grown into.
+        while self.level(self.root) < j + 3 || !self.padded() {
+            self.expand();
+        }
+
+        let level = self.level(self.root);
+        self.root = self.step_node(self.root, j);
+        let offset = 1i64 << (level - 2);
+        self.origin = (self.origin.0 + offset, self.origin.1 + offset);
+        self.generation += 1 << j;
+    }
+
+    // The node's level, and so its size.
+    fn level(
+        &self,
+        id: NodeId,
+    ) -> u32 {
+        self.nodes[id as usize].level
+    }
+
+    fn node(
+        &self,
+        id: NodeId,
+    ) -> Node {
+        self.nodes[id as usize]
+    }
+
+    // The node made of these four, which must all be at the same level.
+    fn join(
+        &mut self,
+        nw: NodeId,
+        ne: NodeId,
+        sw: NodeId,
+        se: NodeId,
+    ) -> NodeId {
+        let node = Node { level: self.level(nw) + 1, nw, ne, sw, se };
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        let population = [nw, ne, sw, se].iter()
+            .map(|&child| self.populations[child as usize])
+            .fold(0u64, u64::saturating_add);
+        self.nodes.push(node);
+        self.populations.push(population);
+        self.ids.insert(node, id);
+        id
+    }
+
+    fn empty(
+        &mut self,
+        level: u32,
+    ) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let next = self.join(e, e, e, e);
+            self.empty.push(next);
+        }
+        self.empty[level as usize]
+    }
+
+    // Build the node at the given level whose top left cell is at (x, y)
+    // in the grid.
+    fn build(
+        &mut self,
+        cells: &[f32],
+        level: u32,
+        x: u32,
+        y: u32,
+    ) -> NodeId {
+        let (w, h) = (self.dimensions.width(), self.dimensions.height());
+        if x >= w || y >= h {
+            return self.empty(level);
+        }
+        if level == 0 {
+            return if cells[(y * w + x) as usize] > self.params.threshold { ALIVE } else { DEAD };
+        }
+        let half = 1 << (level - 1);
+        let nw = self.build(cells, level - 1, x, y);
+        let ne = self.build(cells, level - 1, x + half, y);
+        let sw = self.build(cells, level - 1, x, y + half);
+        let se = self.build(cells, level - 1, x + half, y + half);
+        self.join(nw, ne, sw, se)
+    }
+
+    // Copy the live cells of the node whose top left cell is at (x, y) into
+    // the part of the universe given by view, as (left, top, width, height).
+    fn fill(
+        &self,
+        cells: &mut [f32],
+        view: (i64, i64, i64, i64),
+        id: NodeId,
+        x: i64,
+        y: i64,
+    ) {
+        let (left, top, width, height) = view;
+        let size = 1i64 << self.level(id);
+        if self.populations[id as usize] == 0
+            || x >= left + width || y >= top + height || x + size <= left || y + size <= top {
+            return;
+        }
+        if id == ALIVE {
+            cells[((y - top) * width + (x - left)) as usize] = 1.0;
+            return;
+        }
+        let node = self.node(id);
+        let half = size / 2;
+        self.fill(cells, view, node.nw, x, y);
+        self.fill(cells, view, node.ne, x + half, y);
+        self.fill(cells, view, node.sw, x, y + half);
+        self.fill(cells, view, node.se, x + half, y + half);
+    }
+
+    // Whether every live cell is in the middle quarter of the root, by
+    // width, so that 2^(level - 3) generations can't take any of them out
+    // of the middle half.
+    fn padded(&self) -> bool {
+        if self.level(self.root) < MIN_ROOT_LEVEL {
+            return false;
+        }
+        let root = self.node(self.root);
+        let (nw, ne, sw, se) =
+            (self.node(root.nw), self.node(root.ne), self.node(root.sw), self.node(root.se));
+        let inner = [self.node(nw.se).se, self.node(ne.sw).sw,
+                     self.node(sw.ne).ne, self.node(se.nw).nw];
+        let inner_population: u64 = inner.iter().map(|&id| self.populations[id as usize]).sum();
+        inner_population == self.population()
+    }
+
+    // Double the size of the root, keeping its cells in the middle.
+    fn expand(&mut self) {
+        let level = self.level(self.root);
+        let root = self.node(self.root);
+        let e = self.empty(level - 1);
+        let nw = self.join(e, e, e, root.nw);
+        let ne = self.join(e, e, root.ne, e);
+        let sw = self.join(e, root.sw, e, e);
+        let se = self.join(root.se, e, e, e);
+        self.root = self.join(nw, ne, sw, se);
+        let offset = 1i64 << (level - 1);
+        self.origin = (self.origin.0 - offset, self.origin.1 - offset);
+    }
+
+    // The middle of the node, at half its size.
+    fn center(
+        &mut self,
+        id: NodeId,
+    ) -> NodeId {
+        let node = self.node(id);
+        let (nw, ne, sw, se) =
+            (self.node(node.nw), self.node(node.ne), self.node(node.sw), self.node(node.se));
+        self.join(nw.se, ne.sw, sw.ne, se.nw)
+    }
+
+    // The middle of the node at level k, at half its size, after 2^j
+    // generations, where j is at most k - 2. The middle can only be
+    // affected by cells within 2^(k - 2) of it, which are all in the node.
+    fn step_node(
+        &mut self,
+        id: NodeId,
+        j: u32,
+    ) -> NodeId {
+        let level = self.level(id);
+        if self.populations[id as usize] == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(id, j)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.step_leaf(id)
+        } else {
+            // The nine overlapping nodes at level k - 1 that cover the node:
+            // its four quarters, and the five that straddle them.
+            let node = self.node(id);
+            let (nw, ne, sw, se) =
+                (self.node(node.nw), self.node(node.ne), self.node(node.sw), self.node(node.se));
+            let n01 = self.join(nw.ne, ne.nw, nw.se, ne.sw);
+            let n10 = self.join(nw.sw, nw.se, sw.nw, sw.ne);
+            let n11 = self.join(nw.se, ne.sw, sw.ne, se.nw);
+            let n12 = self.join(ne.sw, ne.se, se.nw, se.ne);
+            let n21 = self.join(sw.ne, se.nw, sw.se, se.sw);
+            let nine = [node.nw, n01, node.ne, n10, n11, n12, node.sw, n21, node.se];
+
+            // Take each of those to level k - 2: either by stepping it, for
+            // the first half of the generations when stepping the most this
+            // node can, or by just taking its middle.
+            let mut a = [DEAD; 9];
+            for (a, &n) in a.iter_mut().zip(nine.iter()) {
+                *a = if j == level - 2 { self.step_node(n, level - 3) } else { self.center(n) };
+            }
+
+            // Then put those together as four nodes at level k - 1, and step
+            // each of them through the rest of the generations.
+            let j = j.min(level - 3);
+            let quarters = [(0, 1, 3, 4), (1, 2, 4, 5), (3, 4, 6, 7), (4, 5, 7, 8)];
+            let mut r = [DEAD; 4];
+            for (r, &(q0, q1, q2, q3)) in r.iter_mut().zip(quarters.iter()) {
+                let quarter = self.join(a[q0], a[q1], a[q2], a[q3]);
+                *r = self.step_node(quarter, j);
+            }
+            self.join(r[0], r[1], r[2], r[3])
+        };
+
+        self.results.insert((id, j), result);
+        result
+    }
+
+    // Step the middle 2x2 cells of a 4x4 node by one generation.
+    fn step_leaf(
+        &mut self,
+        id: NodeId,
+    ) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = self.cell_in(id, x as u64, y as u64);
+            }
+        }
+
+        let p = &self.params;
+        let next = |x: usize, y: usize| {
+            let mut count = 0;
+            for (dx, dy) in [(0, 0), (1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)] {
+                count += cells[y + dy - 1][x + dx - 1] as u32;
+            }
+            let live = if cells[y][x] {
+                in_set(count, p.survival, p.survival_min, p.survival_max)
+            } else {
+                in_set(count, p.birth, p.birth_min, p.birth_max)
+            };
+            if live { ALIVE } else { DEAD }
+        };
+        let (nw, ne, sw, se) = (next(1, 1), next(2, 1), next(1, 2), next(2, 2));
+        self.join(nw, ne, sw, se)
+    }
+
+    // Whether the cell at (x, y) within the node is alive.
+    fn cell_in(
+        &self,
+        id: NodeId,
+        x: u64,
+        y: u64,
+    ) -> bool {
+        let level = self.level(id);
+        if level == 0 {
+            return id == ALIVE;
+        }
+        let node = self.node(id);
+        let half = 1 << (level - 1);
+        match (x < half, y < half) {
+            (true, true) => self.cell_in(node.nw, x, y),
+            (false, true) => self.cell_in(node.ne, x - half, y),
+            (true, false) => self.cell_in(node.sw, x, y - half),
+            (false, false) => self.cell_in(node.se, x - half, y - half),
+        }
+    }
+
+    // Throw away every node that isn't part of the root or of the imported
+    // grid, and every remembered result.
+    fn collect(&mut self) {
+        let old = std::mem::take(&mut self.nodes);
+        let old_populations = std::mem::take(&mut self.populations);
+        self.nodes = old[..2].to_vec();
+        self.populations = old_populations[..2].to_vec();
+        self.ids.clear();
+        self.results.clear();
+        self.empty.truncate(1);
+
+        let mut copied = HashMap::new();
+        self.root = self.copy(&old, &mut copied, self.root);
+        self.start.0 = self.copy(&old, &mut copied, self.start.0);
+    }
+
+    // Copy a node from the old arena into the current one.
+    fn copy(
+        &mut self,
+        old: &[Node],
+        copied: &mut HashMap<NodeId, NodeId>,
+        id: NodeId,
+    ) -> NodeId {
+        if id == DEAD || id == ALIVE {
+            return id;
+        }
+        if let Some(&new) = copied.get(&id) {
+            return new;
+        }
+        let node = old[id as usize];
+        let nw = self.copy(old, copied, node.nw);
+        let ne = self.copy(old, copied, node.ne);
+        let sw = self.copy(old, copied, node.sw);
+        let se = self.copy(old, copied, node.se);
+        let new = self.join(nw, ne, sw, se);
+        copied.insert(id, new);
+        new
+    }
+}
+
+// Whether these parameters are for a rule that Hashlife can run.
+fn check(params: &Params) -> Result<(), String> {
+    if params.lenia != 0 || params.states != 2 || params.radius != 1
+        || params.von_neumann != 0 || params.include_center != 0 {
+        return Err("Hashlife only works for two-state rules like B3/S23".to_string());
+    }
+    if in_set(0, params.birth, params.birth_min, params.birth_max) {
+        return Err("Hashlife can't run rules with B0".to_string());
+    }
+    Ok(())
+}
+
+#[test]
+fn step_patterns() {
+    use crate::{cpu_life::CpuLife, life::LifeGrid, rule::Rule, topology::Topology};
+
+    let dim = Dimensions::new(64, 64);
+    let params = |topology: &str| Params::new(dim, 0.7, 0.0, &Rule::conway(),
+        &Topology::parse(topology).unwrap());
+    let grid = |cells: &[(u32, u32)]| {
+        let mut grid = vec![0.0; dim.area()];
+        for &(x, y) in cells {
+            grid[(y * dim.width() + x) as usize] = 1.0;
+        }
+        grid
+    };
+
+    // A glider moves one cell diagonally every four generations, forever.
+    let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+    let mut life = Hashlife::new(dim, &params("T")).unwrap();
+    life.import(grid(&glider));
+    life.advance(4);
+    assert_eq!(life.viewport(1, 1, dim), grid(&glider));
+    life.advance((1 << 40) - 4);
+    assert_eq!(life.generation(), 1 << 40);
+    assert_eq!(life.population(), 5);
+    assert_eq!(life.viewport(1 << 38, 1 << 38, dim), grid(&glider));
+    assert_eq!(life.export(), grid(&[]));
+    assert_eq!(life.population_at(2), 5);
+    assert_eq!(life.export(), grid(&[(2, 1), (0, 2), (2, 2), (1, 3), (2, 3)]));
+
+    // Until anything reaches the edges of a bounded grid, stepping it on
+    // the CPU gives the same result.
+    let r_pentomino = [(31, 30), (32, 30), (30, 31), (31, 31), (31, 32)];
+    let mut cpu = CpuLife::new(dim, &params("P64,64"));
+    cpu.import(&(), grid(&r_pentomino));
+    let mut life = Hashlife::new(dim, &params("P64,64")).unwrap();
+    life.import(grid(&r_pentomino));
+    cpu.step_n(&(), 24);
+    life.step_pow2(4);
+    life.step_pow2(3);
+    assert!(cpu.verify(&life.export()).is_ok());
+
+    // The R-pentomino settles down to 116 cells after 1103 generations,
+    // having thrown out six gliders.
+    assert_eq!(life.population_at(1103), 116);
+    assert_eq!(life.population_at(5000), 116);
+
+    let bosco = Params::new(dim, 0.7, 0.0, &Rule::parse("R5,C0,M1,S34..58,B34..45,NM")
+        .unwrap(), &Topology::torus());
+    assert!(Hashlife::new(dim, &bosco).is_err());
+}
+
+#[test]
+fn reject_b0() {
+    use crate::{rule::Rule, topology::Topology};
+
+    // Under B0, empty space comes alive, which an empty block never does.
+    let dim = Dimensions::new(64, 64);
+    let params = |rule: &str| Params::new(dim, 0.7, 0.0, &Rule::parse(rule).unwrap(),
+        &Topology::torus());
+    assert_eq!(Hashlife::new(dim, &params("B0123478/S01234678")).err(),
+        Some("Hashlife can't run rules with B0".to_string()));
+    let mut life = Hashlife::new(dim, &params("B3/S23")).unwrap();
+    assert!(life.set_params(&params("B03/S23")).is_err());
+}
@@ -0,0 +1,181 @@
+// A record of earlier generations of the grid, so that it can be wound back.
+//
+// Every so many generations, a copy of the cells is kept on the GPU as a
+// keyframe. Any generation since the oldest keyframe can then be got back
+// by copying in the latest keyframe before it and stepping on from there,
+// which gives the same cells as the first time round, since stepping is
+// deterministic. When the cells are changed by hand, a keyframe is taken
+// of the changed cells, and any later ones are thrown away, since they no
+// longer follow. Anything else which would change what replaying gives,
+// such as changing the parameters, has to start the history again.
+//
+// Keyframes are kept within a memory budget; when it's full, the oldest
+// is thrown away to make room for the next. Winding back doesn't throw any
+// away, so the grid can be scrubbed forward again through the keyframes
+// after the one it was wound back to.
+
+use std::collections::VecDeque;
+
+use crate::life::Life;
+
+// How many generations apart keyframes are taken, at least. Going back to
+// any generation means stepping up to this many generations again.
+const KEYFRAME_INTERVAL: usize = 64;
+
+struct Keyframe {
+    generation: usize,
+    cells: wgpu::Buffer,
+}
+
+pub struct History {
+    // Oldest first.
+    keyframes: VecDeque<Keyframe>,
+    // How many keyframes fit in the budget.
+    capacity: usize,
+    // The size of each keyframe.
+    size: u64,
+}
+
+impl History {
+    // A history of the given Life grid, which can use up to the given number
+    // of bytes of GPU memory, or None if that's not enough for a keyframe.
+    pub fn new(
+        life: &Life,
+        budget: u64,
+    ) -> Option<Self> {
+        let size = life.buffer_size();
+        let capacity = (budget / size) as usize;
+        if capacity == 0 {
+            return None;
+        }
+        Some(History {
+            keyframes: VecDeque::new(),
+            capacity,
+            size,
+        })
+    }
+
+    // Take a keyframe of the current generation, if it's been long enough
+    // since the newest one.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        life: &Life,
+    ) {
+        let due = match self.keyframes.back() {
+            Some(newest) => life.frame_num() >= newest.generation + KEYFRAME_INTERVAL,
+            None => true,
+        };
+        if due {
+            self.push(device, command_encoder, life);
+        }
+    }
+
+    // Take a keyframe of cells which have just been changed by hand,
+    // throwing away any from this generation on, which came before the
+    // change.
+    pub fn edited(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        life: &Life,
+    ) {
+        while matches!(self.keyframes.back(), Some(k) if k.generation >= life.frame_num()) {
+            self.keyframes.pop_back();
+        }
+        self.push(device, command_encoder, life);
+    }
+
+    // Forget everything before the current generation.
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    // Put the grid back to how it was at the given generation, which must
+    // be no older than the oldest keyframe.
+    pub fn rewind(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        life: &mut Life,
+        generation: usize,
+    ) -> Result<(), String> {
+        let keyframe = self.keyframes.iter().rev()
+            .find(|k| k.generation <= generation)
+            .ok_or_else(|| match self.keyframes.front() {
+                Some(oldest) => format!("the history only goes back to generation {}",
+                    oldest.generation),
+                None => "there's no history yet".to_string(),
+            })?;
+        life.restore(command_encoder, &keyframe.cells, keyframe.generation);
+        life.step_n(command_encoder, (generation - keyframe.generation) as u32);
+        Ok(())
+    }
+
+    // The generation of the keyframe the given number of keyframes after
+    // the given generation, or before it if the number is negative, if the
+    // history has one there.
+    pub fn keyframe_from(
+        &self,
+        generation: usize,
+        keyframes: i32,
+    ) -> Option<usize> {
+        keyframe_from(self.keyframes.iter().map(|k| k.generation), generation, keyframes)
+    }
+
+    fn push(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        life: &Life,
+    ) {
+        // Reuse the oldest keyframe's buffer once the budget is used up.
+        let cells = if self.keyframes.len() >= self.capacity {
+            self.keyframes.pop_front().unwrap().cells
+        } else {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("history keyframe"),
+                size: self.size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        command_encoder.copy_buffer_to_buffer(life.src_buf(), 0, &cells, 0, self.size);
+        self.keyframes.push_back(Keyframe {
+            generation: life.frame_num(),
+            cells,
+        });
+    }
+}
+
+// The generation of the keyframe the given number of keyframes after the
+// given generation, or before it, out of keyframes taken at the given
+// generations, oldest first. A keyframe of the generation itself doesn't
+// count.
+fn keyframe_from(
+    generations: impl DoubleEndedIterator<Item = usize>,
+    generation: usize,
+    keyframes: i32,
+) -> Option<usize> {
+    let n = keyframes.unsigned_abs() as usize;
+    if n == 0 {
+        return Some(generation);
+    }
+    if keyframes > 0 {
+        generations.filter(|&g| g > generation).nth(n - 1)
+    } else {
+        generations.rev().filter(|&g| g < generation).nth(n - 1)
+    }
+}
+
+#[test]
+fn scrub() {
+    let generations = || vec![0, 64, 128, 200, 264].into_iter();
+    assert_eq!(keyframe_from(generations(), 150, -1), Some(128));
+    assert_eq!(keyframe_from(generations(), 128, -1), Some(64));
+    assert_eq!(keyframe_from(generations(), 128, -2), Some(0));
+    assert_eq!(keyframe_from(generations(), 128, -3), None);
+    assert_eq!(keyframe_from(generations(), 128, 2), Some(264));
+    assert_eq!(keyframe_from(generations(), 264, 1), None);
+    assert_eq!(keyframe_from(generations(), 70, 0), Some(70));
+}
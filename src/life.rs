@@ -1,31 +1,240 @@
 // Conway's Game of Life
 
-use std::{borrow::Cow, mem};
+use std::{borrow::Cow, mem, ops::Range, str::FromStr};
 use wgpu::util::DeviceExt;
 
 use crate::{
+    debug_buffer::DebugBuffer,
     dimensions::Dimensions,
     directions::{RenderDir, RenderMotion, RenderSources},
-    life_params::LifeParams,
+    edit::CellEdit,
+    life_params::{LifeParams, Params},
     texture::Texture,
+    topology::Boundary,
 };
 
 // ---------------------------------------------------------------------------
 // Data that is shared between Rust and the compute pipeline in WGSL.
 
 // Number of cells calculated in each gpu work group.
-// This must match the value of the workgroup_size() annotation in life.wgsl
-const WORKGROUP_SIZE: (u32, u32) = (8, 8);
+// The shaders refer to this through placeholders, which shader_source()
+// fills in, so that they always agree with it.
+pub const WORKGROUP_SIZE: (u32, u32) = (8, 8);
+
+// The width and height of the block of cells that life_blocked() steps in
+// each workgroup, and how many generations it steps them by. Each pass
+// writes out the middle (BLOCK_WIDTH - 2 * BLOCK_STEPS) cells square.
+const BLOCK_WIDTH: u32 = 16;
+const BLOCK_STEPS: u32 = 4;
+
+// Fill in the placeholders in a shader's source: the size of a workgroup,
+// the size of the tile that life_tiled() loads for each workgroup, which
+// has a border one cell wide, and the sizes used by life_blocked().
+pub fn shader_source(template: &str) -> String {
+    let (width, height) = WORKGROUP_SIZE;
+    template
+        .replace("$WORKGROUP_WIDTH", &width.to_string())
+        .replace("$WORKGROUP_HEIGHT", &height.to_string())
+        .replace("$TILE_WIDTH", &(width + 2).to_string())
+        .replace("$TILE_AREA", &((width + 2) * (height + 2)).to_string())
+        .replace("$BLOCK_WIDTH", &BLOCK_WIDTH.to_string())
+        .replace("$BLOCK_STEPS", &BLOCK_STEPS.to_string())
+        .replace("$BLOCK_AREA", &(BLOCK_WIDTH * BLOCK_WIDTH).to_string())
+        .replace("$BLOCK_CELLS", &(2 * BLOCK_WIDTH * BLOCK_WIDTH).to_string())
+}
+
+// ---------------------------------------------------------------------------
+
+// How cells are stored in the cell buffers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Representation {
+    // One f32 per cell, which works for every rule.
+    Float,
+    // One bit per cell, packed 32 to a u32; see life_packed.wgsl. This needs
+    // an eighth of the memory bandwidth, but only works for two-state rules
+    // with the eight-cell Moore neighborhood, on grids whose width is a
+    // multiple of 32 and whose wrapping edges aren't flipped or shifted.
+    // Cells are either alive or dead, so they don't fade as they age.
+    Packed,
+}
+
+impl Representation {
+    // Check that cells with these parameters can be stored this way.
+    pub fn check(
+        &self,
+        params: &Params,
+    ) -> Result<(), String> {
+        if *self == Representation::Float {
+            return Ok(());
+        }
+        if params.lenia != 0 || params.states != 2 || params.radius != 1
+            || params.von_neumann != 0 || params.include_center != 0 {
+            return Err("packed cells only work for two-state rules like B3/S23".to_string());
+        }
+        if params.width % 32 != 0 {
+            return Err(format!("packed cells need a grid width which is a multiple of 32, not {}",
+                params.width));
+        }
+        // Edges are only flipped or shifted where they wrap around; dead and
+        // mirrored edges ignore the topology's twists.
+        let wraps = params.boundary == Boundary::Wrap as u32;
+        if wraps && (params.flip_x != 0 || params.flip_y != 0
+            || params.shift_x != 0 || params.shift_y != 0) {
+            return Err("packed cells don't work with twisted or shifted edges".to_string());
+        }
+        Ok(())
+    }
+
+    // How many cells are stored in each element of the cell buffers.
+    fn cells_per_element(&self) -> u32 {
+        match self {
+            Representation::Float => 1,
+            Representation::Packed => 32,
+        }
+    }
+
+    // The size in bytes of a cell buffer for a grid of the given size.
+    fn buffer_size(
+        &self,
+        dimensions: Dimensions,
+    ) -> u64 {
+        (dimensions.area() * mem::size_of::<f32>() / self.cells_per_element() as usize) as u64
+    }
+}
+
+// Which compute kernel steps float cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kernel {
+    // Each invocation reads its cell's neighbors from the cell buffer.
+    Direct,
+    // Each workgroup first loads its cells and their neighbors into
+    // workgroup memory; see life_tiled() in life.wgsl. This only works for
+    // Life-like rules of radius 1; other rules use the direct kernel.
+    Tiled,
+    // The same as Tiled, but step_n() also steps several generations in
+    // each pass where it can; see life_blocked() in life.wgsl. That only
+    // works where the edges of the grid aren't flipped or shifted; on
+    // other grids this is the same as Tiled.
+    Blocked,
+}
+
+impl Kernel {
+    // The kernel which steps cells with these parameters, when this is the
+    // one asked for.
+    fn choose(
+        &self,
+        representation: Representation,
+        p: &Params,
+    ) -> Kernel {
+        if representation != Representation::Float || *self == Kernel::Direct
+            || p.lenia != 0 || p.radius != 1 {
+            return Kernel::Direct;
+        }
+        let twisted = p.flip_x != 0 || p.flip_y != 0 || p.shift_x != 0 || p.shift_y != 0;
+        if *self == Kernel::Blocked && (p.boundary != 0 || !twisted) {
+            Kernel::Blocked
+        } else {
+            Kernel::Tiled
+        }
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(Kernel::Direct),
+            "tiled" => Ok(Kernel::Tiled),
+            "blocked" => Ok(Kernel::Blocked),
+            _ => Err(format!("kernel \"{}\" should be direct, tiled or blocked", s)),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+
+// How many generations run() steps at a time. On the GPU, each batch is
+// recorded in a command buffer, and submitted before the next.
+const STEPS_PER_SUBMIT: u32 = 100;
+
+// What a Life grid can do, whatever it runs on: Life on the GPU, given a
+// Gpu to run on, and CpuLife on the CPU, which needs nothing, so is given
+// (). Code which only imports, steps and exports a grid can run on either.
+pub trait LifeGrid<C> {
+    // Import some data into the Life grid.
+    fn import(
+        &mut self,
+        context: &C,
+        cells: Vec<f32>,
+    );
+
+    // Export the current contents of the Life grid.
+    fn export(
+        &self,
+        context: &C,
+    ) -> Vec<f32>;
+
+    // Step the given number of generations, and wait for them to finish.
+    fn step_n(
+        &mut self,
+        context: &C,
+        generations: u32,
+    );
+
+    // Take on new parameters, if the grid can be stepped with them.
+    fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String>;
+
+    fn frame_num(&self) -> usize;
+
+    // Run any number of generations, a batch at a time.
+    fn run(
+        &mut self,
+        context: &C,
+        generations: u64,
+    ) {
+        let mut remaining = generations;
+        while remaining > 0 {
+            let steps = remaining.min(STEPS_PER_SUBMIT.into()) as u32;
+            self.step_n(context, steps);
+            remaining -= u64::from(steps);
+        }
+    }
+}
+
+// What Life runs on.
+pub type Gpu<'a> = (&'a wgpu::Device, &'a wgpu::Queue);
 
 // ---------------------------------------------------------------------------
 
 pub struct Life {
     // Data for the compute shader.
+    representation: Representation,
+    // The kernel that was asked for, and the one in use, which depends on
+    // the parameters.
+    requested_kernel: Kernel,
+    kernel: Kernel,
+    row_count_pipeline: Option<wgpu::ComputePipeline>,
     compute_pipeline: wgpu::ComputePipeline,
+    tiled_pipeline: Option<wgpu::ComputePipeline>,
+    blocked_pipeline: Option<wgpu::ComputePipeline>,
+    show_pipeline: wgpu::ComputePipeline,
     bind_groups: RenderMotion<wgpu::BindGroup>,
     dimensions: Dimensions,
+    threshold: f32,
     cell_buffers: RenderSources<wgpu::Buffer>,
+    _row_count_buffer: wgpu::Buffer,
     frame_num: usize,
+    // How many passes have been run, which says which of the cell buffers
+    // holds the current generation. This differs from frame_num once a
+    // pass has stepped more than one generation.
+    pass_num: usize,
+    // Whether the cells have been changed since they were last written to
+    // the texture.
+    stale: bool,
 }
 
 impl Life {
@@ -34,16 +243,24 @@ impl Life {
         dimensions: Dimensions,
         params: &LifeParams,
         texture: &Texture,
+        representation: Representation,
+        kernel: Kernel,
     ) -> Self {
+        representation.check(params.params()).expect("unsupported cell representation");
+
         // Load and compile the compute shader.
+        let source = match representation {
+            Representation::Float => include_str!("life.wgsl"),
+            Representation::Packed => include_str!("life_packed.wgsl"),
+        };
         let compute_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("life.wgsl"))),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader_source(source))),
         });
 
         // Allocate a pair of equal-sized GPU buffers to hold cell data.
         // COPY_SRC is used so they can be read from for debugging.
-        let cell_bufsize = dimensions.area() * mem::size_of::<f32>();
+        let cell_bufsize = representation.buffer_size(dimensions) as usize;
         let cell_buffers = RenderSources::new(|dir|
             device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some(&format!("Source for {:?}", dir)),
@@ -55,6 +272,19 @@ impl Life {
                 mapped_at_creation: false,
             }));
 
+        // Allocate a buffer for the per-row neighbor counts used by
+        // Larger-than-Life rules. Packed cells never need them.
+        let row_count_bufsize = match representation {
+            Representation::Float => dimensions.area() * mem::size_of::<u32>(),
+            Representation::Packed => mem::size_of::<u32>(),
+        };
+        let row_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Row counts"),
+            usage: wgpu::BufferUsages::STORAGE,
+            size: row_count_bufsize as _,
+            mapped_at_creation: false,
+        });
+
         // Create the bind group layout and compute pipeline for the life algorithm.
         let compute_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -100,6 +330,19 @@ impl Life {
                         ty: texture.binding_type(wgpu::StorageTextureAccess::WriteOnly),
                         count: None,
                     },
+
+                    // Binding for the global variable "rowCounts".
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size:
+                                wgpu::BufferSize::new(row_count_bufsize as _),
+                        },
+                        count: None,
+                    },
                 ],
                 label: None,
             });
@@ -109,12 +352,25 @@ impl Life {
                 bind_group_layouts: &[&compute_bind_group_layout],
                 push_constant_ranges: &[],
             });
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("life compute pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: "life",
-        });
+        let entry_pipeline = |label, entry_point|
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: &compute_shader,
+                entry_point,
+            });
+        let compute_pipeline = entry_pipeline("life compute pipeline", "life");
+
+        // The other kernels for float cells are all built, even if they
+        // can't be used with these parameters, since they may change.
+        let float_pipeline = |label, entry_point| match representation {
+            Representation::Float => Some(entry_pipeline(label, entry_point)),
+            Representation::Packed => None,
+        };
+        let row_count_pipeline = float_pipeline("life row count pipeline", "row_counts");
+        let tiled_pipeline = float_pipeline("life tiled pipeline", "life_tiled");
+        let blocked_pipeline = float_pipeline("life blocked pipeline", "life_blocked");
+        let show_pipeline = entry_pipeline("life show pipeline", "show");
 
         // Create a RenderMotion of bind groups to map the RenderSources of cell_buffers.
         let bind_groups = RenderMotion::new(|dir|
@@ -137,20 +393,47 @@ impl Life {
                         binding: 3,
                         resource: texture.binding_resource(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: row_count_buffer.as_entire_binding(),
+                    },
                 ],
                 label: None,
             })
         );
 
         Life {
+            representation,
+            requested_kernel: kernel,
+            kernel: kernel.choose(representation, params.params()),
+            row_count_pipeline,
             compute_pipeline,
+            tiled_pipeline,
+            blocked_pipeline,
+            show_pipeline,
             bind_groups,
             dimensions,
+            threshold: params.params().threshold,
             cell_buffers,
+            _row_count_buffer: row_count_buffer,
             frame_num: 0,
+            pass_num: 0,
+            stale: true,
         }
     }
 
+    // Take on new parameters, if cells stored this way can be stepped with
+    // them; the caller is responsible for uploading them.
+    pub fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        self.representation.check(params)?;
+        self.threshold = params.threshold;
+        self.kernel = self.requested_kernel.choose(self.representation, params);
+        Ok(())
+    }
+
     /// update is called for any WindowEvent not handled by the framework
     fn _update(
         &mut self,
@@ -159,31 +442,55 @@ impl Life {
         // empty
     }
 
-    /// resize is called on WindowEvent::Resized events
-    fn _resize(
+    // Start again with a grid of another size, given by the parameters,
+    // holding the given cells. Everything on the GPU is made anew, but the
+    // generation count carries on.
+    pub fn resize(
         &mut self,
-        _sc_desc: &wgpu::SwapChainDescriptor,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        params: &LifeParams,
+        texture: &Texture,
+        cells: Vec<f32>,
     ) {
-        // empty
+        let dimensions = Dimensions::new(params.params().width, params.params().height);
+        let frame_num = self.frame_num;
+        *self = Life::new(device, dimensions, params, texture,
+            self.representation, self.requested_kernel);
+        self.frame_num = frame_num;
+        self.import(device, queue, cells);
+    }
+
+    pub fn representation(&self) -> Representation {
+        self.representation
     }
 
     // Import some data into the Life grid.
     pub fn import(
-        &self,
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         cells: Vec<f32>,
     ) {
         assert_eq!(cells.len(), self.dimensions.area());
 
+        let contents = match self.representation {
+            Representation::Float => bytemuck::cast_slice(&cells).to_vec(),
+            Representation::Packed => {
+                let words: Vec<u32> = cells.chunks(32).map(|chunk|
+                    chunk.iter().enumerate()
+                        .filter(|(_, &v)| v > self.threshold)
+                        .fold(0, |word, (bit, _)| word | 1 << bit)
+                ).collect();
+                bytemuck::cast_slice(&words).to_vec()
+            }
+        };
         let import_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("cell data import buffer"),
             usage: wgpu::BufferUsages::VERTEX
                  | wgpu::BufferUsages::STORAGE
                  | wgpu::BufferUsages::COPY_SRC,
-            contents: bytemuck::cast_slice(&cells),
+            contents: &contents,
             });
 
         let mut command_encoder =
@@ -191,14 +498,132 @@ impl Life {
                 label: Some("filling Life grid")
             });
 
-        let next_src = &self.cell_buffers.src(RenderDir::dir(self.frame_num));
+        let next_src = &self.cell_buffers.src(RenderDir::dir(self.pass_num));
 
         command_encoder.copy_buffer_to_buffer(
             &import_buf, 0, next_src, 0,
-            bytemuck::cast_slice::<_, u8>(&cells).len() as u64
+            contents.len() as u64
         );
 
         queue.submit(Some(command_encoder.finish()));
+        self.stale = true;
+    }
+
+    // Read back some cells of the Life grid, given by column and row. Float
+    // cells are copied out of the current generation a run at a time, where
+    // they're next to each other along a row, rather than reading back the
+    // whole grid.
+    pub fn read_cells(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cells: &[(u32, u32)],
+    ) -> Vec<f32> {
+        let width = self.dimensions.width() as usize;
+        match self.representation {
+            Representation::Float => {
+                if cells.is_empty() {
+                    return Vec::new();
+                }
+                let read_buf = DebugBuffer::new(device, cells.len());
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Life grid cell read")
+                    });
+                for run in row_runs(cells) {
+                    let (x, y) = cells[run.start];
+                    read_buf.enqueue_copyin_range(&mut command_encoder, self.src_buf(),
+                        y as usize * width + x as usize, run.start, run.len());
+                }
+                queue.submit(Some(command_encoder.finish()));
+                read_buf.read(device).expect("failed to read back the Life grid")
+            }
+            Representation::Packed => {
+                let grid = self.export(device, queue);
+                cells.iter().map(|&(x, y)| grid[y as usize * width + x as usize]).collect()
+            }
+        }
+    }
+
+    // Change some cells of the Life grid, in the order given, and return
+    // the edits which would put them back as they were. Float cells are
+    // written straight into the buffer holding the current generation, a
+    // run at a time; packed cells share words, so the grid is read back and
+    // written again.
+    pub fn write_cells(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        edits: &[CellEdit],
+    ) -> Vec<CellEdit> {
+        let width = self.dimensions.width() as usize;
+        let positions: Vec<(u32, u32)> = edits.iter().map(|&(cell, _)| cell).collect();
+        let previous = match self.representation {
+            Representation::Float => {
+                let previous = self.read_cells(device, queue, &positions);
+                for run in row_runs(&positions) {
+                    let (x, y) = positions[run.start];
+                    let offset = (y as usize * width + x as usize) * mem::size_of::<f32>();
+                    let values: Vec<f32> = edits[run].iter().map(|&(_, value)| value).collect();
+                    queue.write_buffer(self.src_buf(), offset as _, bytemuck::cast_slice(&values));
+                }
+                self.stale = true;
+                previous
+            }
+            Representation::Packed => {
+                let mut cells = self.export(device, queue);
+                let previous = positions.iter()
+                    .map(|&(x, y)| cells[y as usize * width + x as usize])
+                    .collect();
+                for &((x, y), value) in edits {
+                    cells[y as usize * width + x as usize] = value;
+                }
+                self.import(device, queue, cells);
+                previous
+            }
+        };
+        positions.into_iter().zip(previous).collect()
+    }
+
+    // Go back to cells kept from an earlier generation, or a later one, by
+    // copying them into the buffer which holds the current generation.
+    pub fn restore(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        cells: &wgpu::Buffer,
+        generation: usize,
+    ) {
+        command_encoder.copy_buffer_to_buffer(cells, 0, self.src_buf(), 0, self.buffer_size());
+        self.frame_num = generation;
+        self.stale = true;
+    }
+
+    // The size in bytes of each of the cell buffers.
+    pub fn buffer_size(&self) -> u64 {
+        self.representation.buffer_size(self.dimensions)
+    }
+
+    // Export the current contents of the Life grid.
+    pub fn export(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<f32> {
+        match self.representation {
+            Representation::Float => {
+                let export_buf = DebugBuffer::new(device, self.dimensions.area());
+                export_buf.copyin(device, queue, self.src_buf());
+                export_buf.read(device).expect("failed to read back the Life grid")
+            }
+            Representation::Packed => {
+                let export_buf = DebugBuffer::<u32>::new(device, self.dimensions.area() / 32);
+                export_buf.copyin(device, queue, self.src_buf());
+                let words = export_buf.read(device).expect("failed to read back the Life grid");
+                words.iter()
+                    .flat_map(|word| (0..32).map(move |bit| ((word >> bit) & 1) as f32))
+                    .collect()
+            }
+        }
     }
 
     pub fn step(
@@ -210,17 +635,109 @@ impl Life {
                 label: Some("Life grid step")
             });
 
-        let xdim = self.dimensions.width() + WORKGROUP_SIZE.0 - 1;
-        let xgroups = xdim / WORKGROUP_SIZE.0;
-        let ydim = self.dimensions.height() + WORKGROUP_SIZE.1 - 1;
-        let ygroups = ydim / WORKGROUP_SIZE.1;
-        let dir = RenderDir::dir(self.frame_num);
+        let (xgroups, ygroups) = self.workgroups();
+        let dir = RenderDir::dir(self.pass_num);
 
-        cpass.set_pipeline(&self.compute_pipeline);
+        // The row count pass does nothing unless the rule needs it, so it's
+        // simplest to always dispatch it.
         cpass.set_bind_group(0, &self.bind_groups.get(dir), &[]);
+        if let Some(row_count_pipeline) = &self.row_count_pipeline {
+            cpass.set_pipeline(row_count_pipeline);
+            cpass.dispatch(xgroups, ygroups, 1);
+        }
+        let compute_pipeline = match self.kernel {
+            Kernel::Direct => &self.compute_pipeline,
+            Kernel::Tiled | Kernel::Blocked => self.tiled_pipeline.as_ref().unwrap(),
+        };
+        cpass.set_pipeline(compute_pipeline);
         cpass.dispatch(xgroups, ygroups, 1);
 
         self.frame_num += 1;
+        self.pass_num += 1;
+        self.stale = false;
+    }
+
+    // Step the given number of generations, in as few passes as possible:
+    // BLOCK_STEPS generations at a time where the blocked kernel is in use,
+    // and one at a time otherwise.
+    pub fn step_n(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        generations: u32,
+    ) {
+        let mut remaining = generations;
+        if self.kernel == Kernel::Blocked {
+            while remaining >= BLOCK_STEPS {
+                self.step_blocked(command_encoder);
+                remaining -= BLOCK_STEPS;
+            }
+        }
+        for _ in 0..remaining {
+            self.step(command_encoder);
+        }
+    }
+
+    // Step BLOCK_STEPS generations in one pass.
+    fn step_blocked(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let blocked_pipeline = self.blocked_pipeline.as_ref().unwrap();
+        let mut cpass =
+            command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Life grid blocked step")
+            });
+
+        // Each workgroup writes out the middle of its block.
+        let out = BLOCK_WIDTH - 2 * BLOCK_STEPS;
+        let xgroups = (self.dimensions.width() + out - 1) / out;
+        let ygroups = (self.dimensions.height() + out - 1) / out;
+        let dir = RenderDir::dir(self.pass_num);
+
+        cpass.set_bind_group(0, &self.bind_groups.get(dir), &[]);
+        cpass.set_pipeline(blocked_pipeline);
+        cpass.dispatch(xgroups, ygroups, 1);
+
+        self.frame_num += BLOCK_STEPS as usize;
+        self.pass_num += 1;
+        self.stale = false;
+    }
+
+    // Bring the texture up to date with the cells, before rendering it.
+    // Float cells are written to the texture as they're computed, so this
+    // only has work to do for them when they've been changed some other
+    // way; packed cells always need it.
+    pub fn show(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if self.representation == Representation::Float && !self.stale {
+            return;
+        }
+        let mut cpass =
+            command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Life grid show")
+            });
+        let (xgroups, ygroups) = self.workgroups();
+        let dir = RenderDir::dir(self.pass_num);
+        cpass.set_bind_group(0, &self.bind_groups.get(dir), &[]);
+        cpass.set_pipeline(&self.show_pipeline);
+        cpass.dispatch(xgroups, ygroups, 1);
+        self.stale = false;
+    }
+
+    // The number of workgroups to dispatch to cover every element of the
+    // cell buffers.
+    fn workgroups(&self) -> (u32, u32) {
+        let columns = self.dimensions.width() / self.representation.cells_per_element();
+        let xdim = columns + WORKGROUP_SIZE.0 - 1;
+        let ydim = self.dimensions.height() + WORKGROUP_SIZE.1 - 1;
+        (xdim / WORKGROUP_SIZE.0, ydim / WORKGROUP_SIZE.1)
+    }
+
+    // The kernel which is actually used to step the cells.
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
     }
 
     #[allow(dead_code)]
@@ -230,11 +747,68 @@ impl Life {
 
     #[allow(dead_code)]
     pub fn src_buf(&self) -> &wgpu::Buffer {
-        self.cell_buffers.src(RenderDir::dir(self.frame_num))
+        self.cell_buffers.src(RenderDir::dir(self.pass_num))
     }
 
     #[allow(dead_code)]
     pub fn dst_buf(&self) -> &wgpu::Buffer {
-        self.cell_buffers.dst(RenderDir::dir(self.frame_num))
+        self.cell_buffers.dst(RenderDir::dir(self.pass_num))
+    }
+}
+
+impl<'a> LifeGrid<Gpu<'a>> for Life {
+    fn import(
+        &mut self,
+        &(device, queue): &Gpu<'a>,
+        cells: Vec<f32>,
+    ) {
+        Life::import(self, device, queue, cells);
+    }
+
+    fn export(
+        &self,
+        &(device, queue): &Gpu<'a>,
+    ) -> Vec<f32> {
+        Life::export(self, device, queue)
+    }
+
+    fn step_n(
+        &mut self,
+        &(device, queue): &Gpu<'a>,
+        generations: u32,
+    ) {
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: None
+            });
+        Life::step_n(self, &mut command_encoder, generations);
+        queue.submit(Some(command_encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+    }
+
+    fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        Life::set_params(self, params)
+    }
+
+    fn frame_num(&self) -> usize {
+        self.frame_num
+    }
+}
+
+// Split a list of cells into runs of cells next to each other along a row,
+// such as the rows of a rectangle, as the parts of the list they take up.
+fn row_runs(cells: &[(u32, u32)]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for end in 1..=cells.len() {
+        let (x, y) = cells[end - 1];
+        if end == cells.len() || cells[end] != (x + 1, y) {
+            runs.push(start..end);
+            start = end;
+        }
     }
+    runs
 }
@@ -3,6 +3,39 @@ struct LifeParams {
     width : u32;
     height : u32;
     threshold : f32;
+    // Bit N of birth (survival) is set if a dead (live) cell with N live
+    // neighbors is alive in the next generation.
+    birth : u32;
+    survival : u32;
+    // Number of cell states. Above 2, a dying cell spends (states - 2)
+    // generations in refractory states, stored as negative values that
+    // count up towards zero.
+    states : u32;
+    // How much a surviving cell fades in each generation.
+    decay : f32;
+    // Larger-than-Life rules give birth and survival as inclusive ranges,
+    // since their neighbor counts don't fit in a bitmask.
+    birth_min : u32;
+    birth_max : u32;
+    survival_min : u32;
+    survival_max : u32;
+    radius : u32;
+    von_neumann : u32;
+    include_center : u32;
+    // Lenia rules are continuous; see lenia() below.
+    lenia : u32;
+    mu : f32;
+    sigma : f32;
+    dt : f32;
+    // How cells beyond the edges are found: 0 wraps around to the opposite
+    // edge, 1 treats them as dead, and 2 reflects the cells inside the edge.
+    // When wrapping, crossing the top or bottom edge can flip or shift x,
+    // and crossing the left or right edge can flip or shift y.
+    boundary : u32;
+    flip_x : u32;
+    flip_y : u32;
+    shift_x : i32;
+    shift_y : i32;
 };
 
 [[block]]
@@ -10,58 +43,426 @@ struct Cells {
     cells : array<f32>;
 };
 
+[[block]]
+struct Counts {
+    counts : array<u32>;
+};
+
 [[group(0), binding(0)]] var<uniform> params : LifeParams;
 [[group(0), binding(1)]] var<storage> cellSrc : [[access(read)]] Cells;
 [[group(0), binding(2)]] var<storage> cellDst : [[access(write)]] Cells;
 [[group(0), binding(3)]] var texture : [[access(write)]] texture_storage_2d<r32float>;
+[[group(0), binding(4)]] var<storage> rowCounts : [[access(read_write)]] Counts;
 
-[[stage(compute), workgroup_size(8, 8)]]
-fn life([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+fn wrap(v: i32, size: i32) -> i32 {
+    return ((v % size) + size) % size;
+}
+
+// Find the cell that (x, y) refers to under the grid's topology, when
+// (x, y) may be beyond the edges. Returns -1 if there is no such cell.
+fn locate(x: i32, y: i32) -> i32 {
+    let W : i32 = i32(params.width);
+    let H : i32 = i32(params.height);
+    var cx : i32 = x;
+    var cy : i32 = y;
+
+    if (params.boundary == 1u32) {
+        if (cx < 0 || cx >= W || cy < 0 || cy >= H) {
+            return -1;
+        }
+        return cy * W + cx;
+    }
+
+    // Mirrored edges repeat the grid and its reflection, so fold over twice
+    // the size first; a radius may reach further than one reflection.
+    if (params.boundary == 2u32) {
+        cx = wrap(cx, 2 * W);
+        if (cx >= W) {
+            cx = 2 * W - 1 - cx;
+        }
+        cy = wrap(cy, 2 * H);
+        if (cy >= H) {
+            cy = 2 * H - 1 - cy;
+        }
+        return cy * W + cx;
+    }
+
+    if (cy < 0 || cy >= H) {
+        if (params.flip_x != 0u32) {
+            cx = W - 1 - cx;
+        }
+        if (cy < 0) {
+            cx = cx - params.shift_x;
+        } else {
+            cx = cx + params.shift_x;
+        }
+        cy = wrap(cy, H);
+    }
+    if (cx < 0 || cx >= W) {
+        if (params.flip_y != 0u32) {
+            cy = H - 1 - cy;
+        }
+        if (cx < 0) {
+            cy = cy - params.shift_y;
+        } else {
+            cy = cy + params.shift_y;
+        }
+        cx = wrap(cx, W);
+        cy = wrap(cy, H);
+    }
+    return cy * W + cx;
+}
+
+// The value of the cell at (x, y).
+fn cell(x: i32, y: i32) -> f32 {
+    let pix : i32 = locate(x, y);
+    if (pix < 0) {
+        return 0.0;
+    }
+    return cellSrc.cells[pix];
+}
+
+// 1 if the cell at (x, y) is alive, 0 otherwise.
+fn alive(x: i32, y: i32) -> u32 {
+    if (cell(x, y) > params.threshold) {
+        return 1u32;
+    }
+    return 0u32;
+}
+
+// Whether count is in the set given by a bitmask and an inclusive range.
+fn in_set(count: u32, mask: u32, lo: u32, hi: u32) -> bool {
+    if (count < 32u32 && (mask & (1u32 << count)) != 0u32) {
+        return true;
+    }
+    return count >= lo && count <= hi;
+}
+
+// Whether the row counts computed by row_counts() are used for this rule.
+fn use_row_counts() -> bool {
+    return params.lenia == 0u32 && params.radius > 1u32 && params.von_neumann == 0u32;
+}
+
+// The row count at (x, y). Row counts respect the topology as cells do,
+// since flipping or shifting a row doesn't change how many cells in it are
+// alive, and the row beyond a mirrored edge is the row just inside it.
+fn row_count(x: i32, y: i32) -> u32 {
+    let pix : i32 = locate(x, y);
+    if (pix < 0) {
+        return 0u32;
+    }
+    return rowCounts.counts[pix];
+}
+
+// The number of live cells in the neighborhood of (x, y).
+fn neighbors(x: i32, y: i32) -> u32 {
+    let R : i32 = i32(params.radius);
+    var count : u32 = 0u32;
+
+    if (use_row_counts()) {
+        for (var dy : i32 = -R; dy <= R; dy = dy + 1) {
+            count = count + row_count(x, y + dy);
+        }
+    } else {
+        for (var dy : i32 = -R; dy <= R; dy = dy + 1) {
+            var reach : i32 = R;
+            if (params.von_neumann != 0u32) {
+                reach = R - abs(dy);
+            }
+            for (var dx : i32 = -reach; dx <= reach; dx = dx + 1) {
+                count = count + alive(x + dx, y + dy);
+            }
+        }
+    }
+
+    if (params.include_center == 0u32) {
+        count = count - alive(x, y);
+    }
+    return count;
+}
+
+// For Larger-than-Life rules with a Moore neighborhood, count the live cells
+// within the radius along each cell's row. The life pass then only has to
+// sum these down each column, so a step costs O(R) per cell, not O(R^2).
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn row_counts([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
     let X : u32 = global_id.x;
     let Y : u32 = global_id.y;
     let W : u32 = params.width;
     let H : u32 = params.height;
-    let thresh : f32 = params.threshold;
+    let R : i32 = i32(params.radius);
 
-    if (X > W || Y > H) {
+    if (X >= W || Y >= H || !use_row_counts()) {
         return;
     }
 
-    var count : i32 = 0;
-    for (var y : i32 = i32(Y - 1u32); y <= i32(Y + 1u32); y = y + 1) {
-        for (var x : i32 = i32(X - 1u32); x <= i32(X + 1u32); x = x + 1) {
-            let yw : u32 = u32(y + i32(H)) % H;
-            let xw : u32 = u32(x + i32(W)) % W;
-            if (cellSrc.cells[yw * W + xw] > thresh) {
-                count = count + 1;
-            }
-        }
+    var count : u32 = 0u32;
+    for (var dx : i32 = -R; dx <= R; dx = dx + 1) {
+        count = count + alive(i32(X) + dx, i32(Y));
     }
+    rowCounts.counts[Y * W + X] = count;
+}
 
-    let pix : u32 = Y * W + X;
-    let ov : f32 = cellSrc.cells[pix];
+// The next value of a cell under a Life-like rule, given its current value
+// and the number of its neighbors which are alive.
+fn transition(ov: f32, count: u32) -> f32 {
+    let thresh : f32 = params.threshold;
     let was_alive : bool = ov > thresh;
     var nv : f32;
 
-    // in the first clause, "3 or 4" includes the center cell
-    if (was_alive && (count == 3 || count == 4)) {
-        if (ov - 0.01 > thresh) {
-            nv = ov - 0.01;
+    if (ov < 0.0) {
+        // Refractory cells can't be born; they just count down to death.
+        nv = min(ov + 1.0, 0.0);
+    } else {
+        if (was_alive) {
+            if (in_set(count, params.survival,
+                    params.survival_min, params.survival_max)) {
+                if (ov - params.decay > thresh) {
+                    nv = ov - params.decay;
+                } else {
+                    nv = ov;
+                }
+            } else {
+                nv = -f32(params.states - 2u32);
+            }
         } else {
-            nv = ov;
+            if (in_set(count, params.birth,
+                    params.birth_min, params.birth_max)) {
+                nv = 1.0;
+            } else {
+                nv = 0.0;
+            }
+        }
+    }
+    return nv;
+}
+
+// The next value of the cell at (x, y) under a Life-like rule.
+fn life_like(x: i32, y: i32) -> f32 {
+    return transition(cell(x, y), neighbors(x, y));
+}
+
+// The Lenia kernel, as a function of distance over the radius: a smooth
+// ring which is zero at the center and the edge, and peaks halfway out.
+fn kernel(r: f32) -> f32 {
+    if (r <= 0.0 || r >= 1.0) {
+        return 0.0;
+    }
+    return exp(4.0 - 1.0 / (r * (1.0 - r)));
+}
+
+// The Lenia growth function: 1 when u is mu, falling off towards -1.
+fn growth(u: f32) -> f32 {
+    let d : f32 = (u - params.mu) / params.sigma;
+    return 2.0 * exp(-0.5 * d * d) - 1.0;
+}
+
+// The next value of the cell at (x, y) under a Lenia rule.
+fn lenia(x: i32, y: i32) -> f32 {
+    let R : i32 = i32(params.radius);
+    var sum : f32 = 0.0;
+    var total : f32 = 0.0;
+
+    for (var dy : i32 = -R; dy <= R; dy = dy + 1) {
+        for (var dx : i32 = -R; dx <= R; dx = dx + 1) {
+            let k : f32 = kernel(length(vec2<f32>(f32(dx), f32(dy))) / f32(R));
+            if (k > 0.0) {
+                sum = sum + k * cell(x + dx, y + dy);
+                total = total + k;
+            }
         }
+    }
+
+    return clamp(cell(x, y) + params.dt * growth(sum / total), 0.0, 1.0);
+}
+
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn life([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let X : u32 = global_id.x;
+    let Y : u32 = global_id.y;
+    let W : u32 = params.width;
+    let H : u32 = params.height;
+
+    if (X >= W || Y >= H) {
+        return;
+    }
+
+    var nv : f32;
+    if (params.lenia != 0u32) {
+        nv = lenia(i32(X), i32(Y));
     } else {
-        if (!was_alive && count == 3) {
-            nv = 1.0;
-        } else {
-            nv = 0.0;
+        nv = life_like(i32(X), i32(Y));
+    }
+
+    cellDst.cells[Y * W + X] = nv;
+
+    textureStore(texture,
+        vec2<i32>(i32(X), i32(Y)),
+        // all channels other than the first are ignored
+        vec4<f32>(nv, 0.0, 0.0, 1.0));
+}
+
+// The cells of a workgroup's block, with a border one cell wide around it,
+// for life_tiled().
+var<workgroup> tile : array<f32, $TILE_AREA>;
+
+// The value of a cell in the tile, from its position relative to the top
+// left corner of the workgroup's block.
+fn tile_cell(x: i32, y: i32) -> f32 {
+    return tile[(y + 1) * $TILE_WIDTH + (x + 1)];
+}
+
+// 1 if the cell in the tile is alive, 0 otherwise.
+fn tile_alive(x: i32, y: i32) -> u32 {
+    if (tile_cell(x, y) > params.threshold) {
+        return 1u32;
+    }
+    return 0u32;
+}
+
+// The same as life() for Life-like rules of radius 1, but each workgroup
+// first loads its block of cells and their neighbors into workgroup memory,
+// so each cell is read from the storage buffer about once rather than up to
+// nine times.
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn life_tiled(
+    [[builtin(global_invocation_id)]] global_id: vec3<u32>,
+    [[builtin(local_invocation_id)]] local_id: vec3<u32>,
+    [[builtin(workgroup_id)]] group_id: vec3<u32>,
+) {
+    let X : u32 = global_id.x;
+    let Y : u32 = global_id.y;
+    let W : u32 = params.width;
+    let H : u32 = params.height;
+    let LX : i32 = i32(local_id.x);
+    let LY : i32 = i32(local_id.y);
+
+    // Every invocation helps load the tile, even those beyond the edges of
+    // the grid, since they all have to reach the barrier.
+    let left : i32 = i32(group_id.x) * $WORKGROUP_WIDTH - 1;
+    let top : i32 = i32(group_id.y) * $WORKGROUP_HEIGHT - 1;
+    for (var i : i32 = LY * $WORKGROUP_WIDTH + LX; i < $TILE_AREA;
+            i = i + $WORKGROUP_WIDTH * $WORKGROUP_HEIGHT) {
+        tile[i] = cell(left + i % $TILE_WIDTH, top + i / $TILE_WIDTH);
+    }
+    workgroupBarrier();
+
+    if (X >= W || Y >= H) {
+        return;
+    }
+
+    var count : u32 = 0u32;
+    for (var dy : i32 = -1; dy <= 1; dy = dy + 1) {
+        for (var dx : i32 = -1; dx <= 1; dx = dx + 1) {
+            if (params.von_neumann == 0u32 || dx == 0 || dy == 0) {
+                count = count + tile_alive(LX + dx, LY + dy);
+            }
         }
     }
+    if (params.include_center == 0u32) {
+        count = count - tile_alive(LX, LY);
+    }
 
-    cellDst.cells[pix] = nv;
+    let nv : f32 = transition(tile_cell(LX, LY), count);
+    cellDst.cells[Y * W + X] = nv;
 
     textureStore(texture,
         vec2<i32>(i32(X), i32(Y)),
         // all channels other than the first are ignored
         vec4<f32>(nv, 0.0, 0.0, 1.0));
 }
+
+// The cells of a workgroup's block for life_blocked(), at two successive
+// generations: the first $BLOCK_AREA hold one, and the rest the other.
+var<workgroup> blocks : array<f32, $BLOCK_CELLS>;
+
+// 1 if the cell at index i of blocks is alive, 0 otherwise.
+fn block_alive(i: i32) -> u32 {
+    if (blocks[i] > params.threshold) {
+        return 1u32;
+    }
+    return 0u32;
+}
+
+// Step Life-like rules of radius 1 by $BLOCK_STEPS generations at once.
+// Each workgroup loads a block of cells with a border $BLOCK_STEPS cells
+// wide into workgroup memory, and steps the whole block there. The cells at
+// the edge of the block are missing neighbors, so after each generation
+// the part of the block which is still correct shrinks by one cell on each
+// side, and at the end only the middle of the block is written out.
+//
+// Cells beyond the edges of the grid are stepped like any others, which
+// gives the same result as stepping the cells they refer to, as long as
+// the edges are neither flipped nor shifted; the Rust side only uses this
+// kernel when that's so. On a bounded plane, they stay dead.
+[[stage(compute), workgroup_size($BLOCK_WIDTH, $BLOCK_WIDTH)]]
+fn life_blocked(
+    [[builtin(local_invocation_id)]] local_id: vec3<u32>,
+    [[builtin(workgroup_id)]] group_id: vec3<u32>,
+) {
+    let K : i32 = $BLOCK_STEPS;
+    let BW : i32 = $BLOCK_WIDTH;
+    let LX : i32 = i32(local_id.x);
+    let LY : i32 = i32(local_id.y);
+    let i : i32 = LY * BW + LX;
+
+    // The cell this invocation looks after, which may be beyond the edges.
+    let X : i32 = i32(group_id.x) * (BW - 2 * K) - K + LX;
+    let Y : i32 = i32(group_id.y) * (BW - 2 * K) - K + LY;
+    let outside : bool = locate(X, Y) < 0;
+
+    blocks[i] = cell(X, Y);
+    workgroupBarrier();
+
+    for (var g : i32 = 1; g <= K; g = g + 1) {
+        let src : i32 = ((g + 1) % 2) * $BLOCK_AREA;
+        let dst : i32 = (g % 2) * $BLOCK_AREA;
+
+        var nv : f32 = blocks[src + i];
+        if (!outside && LX >= g && LX < BW - g && LY >= g && LY < BW - g) {
+            var count : u32 = 0u32;
+            for (var dy : i32 = -1; dy <= 1; dy = dy + 1) {
+                for (var dx : i32 = -1; dx <= 1; dx = dx + 1) {
+                    if (params.von_neumann == 0u32 || dx == 0 || dy == 0) {
+                        count = count + block_alive(src + i + dy * BW + dx);
+                    }
+                }
+            }
+            if (params.include_center == 0u32) {
+                count = count - block_alive(src + i);
+            }
+            nv = transition(blocks[src + i], count);
+        }
+        blocks[dst + i] = nv;
+        workgroupBarrier();
+    }
+
+    if (LX < K || LX >= BW - K || LY < K || LY >= BW - K
+            || X >= i32(params.width) || Y >= i32(params.height)) {
+        return;
+    }
+
+    let nv : f32 = blocks[(K % 2) * $BLOCK_AREA + i];
+    cellDst.cells[Y * i32(params.width) + X] = nv;
+
+    textureStore(texture,
+        vec2<i32>(X, Y),
+        // all channels other than the first are ignored
+        vec4<f32>(nv, 0.0, 0.0, 1.0));
+}
+
+// Write the current cells into the texture, for rendering, when they've
+// been changed without being stepped.
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn show([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let X : u32 = global_id.x;
+    let Y : u32 = global_id.y;
+
+    if (X >= params.width || Y >= params.height) {
+        return;
+    }
+
+    textureStore(texture,
+        vec2<i32>(i32(X), i32(Y)),
+        vec4<f32>(cellSrc.cells[Y * params.width + X], 0.0, 0.0, 1.0));
+}
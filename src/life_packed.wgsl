@@ -0,0 +1,202 @@
+// A bit-packed version of the life() pass in life.wgsl, for two-state rules
+// with the eight-cell Moore neighborhood. Each u32 holds a run of 32 cells
+// from one row, with bit N holding the cell at x = 32 * word + N, and each
+// invocation steps one word. Rather than counting each cell's neighbors in
+// turn, the eight neighbor bits of all 32 cells are summed at once with a
+// bit-sliced adder, where each bit of the count is held in its own word.
+
+[[block]]
+struct LifeParams {
+    width : u32;
+    height : u32;
+    threshold : f32;
+    birth : u32;
+    survival : u32;
+    states : u32;
+    decay : f32;
+    birth_min : u32;
+    birth_max : u32;
+    survival_min : u32;
+    survival_max : u32;
+    radius : u32;
+    von_neumann : u32;
+    include_center : u32;
+    lenia : u32;
+    mu : f32;
+    sigma : f32;
+    dt : f32;
+    boundary : u32;
+    flip_x : u32;
+    flip_y : u32;
+    shift_x : i32;
+    shift_y : i32;
+};
+
+[[block]]
+struct Words {
+    words : array<u32>;
+};
+
+[[group(0), binding(0)]] var<uniform> params : LifeParams;
+[[group(0), binding(1)]] var<storage> cellSrc : [[access(read)]] Words;
+[[group(0), binding(2)]] var<storage> cellDst : [[access(write)]] Words;
+[[group(0), binding(3)]] var texture : [[access(write)]] texture_storage_2d<r32float>;
+
+// The number of words in each row.
+fn row_words() -> i32 {
+    return i32(params.width / 32u32);
+}
+
+// Find the row that y refers to under the grid's topology, when y may be
+// beyond the edges. Returns -1 if there is no such row. Packed grids don't
+// support flipped or shifted edges, so rows are found independently of x.
+fn locate_row(y: i32) -> i32 {
+    let H : i32 = i32(params.height);
+    if (y >= 0 && y < H) {
+        return y;
+    }
+    if (params.boundary == 1u32) {
+        return -1;
+    }
+    if (params.boundary == 2u32) {
+        if (y < 0) {
+            return -1 - y;
+        }
+        return 2 * H - 1 - y;
+    }
+    return ((y % H) + H) % H;
+}
+
+// The word at (wx, y), where wx is within the row but y may not be.
+fn word(wx: i32, y: i32) -> u32 {
+    let row : i32 = locate_row(y);
+    if (row < 0) {
+        return 0u32;
+    }
+    return cellSrc.words[row * row_words() + wx];
+}
+
+// The word to the left of (wx, y). Only its top bit is used, as the
+// neighbor of the first cell of the word at (wx, y).
+fn word_west(wx: i32, y: i32) -> u32 {
+    if (wx > 0) {
+        return word(wx - 1, y);
+    }
+    if (params.boundary == 1u32) {
+        return 0u32;
+    }
+    if (params.boundary == 2u32) {
+        return word(0, y) << 31u32;
+    }
+    return word(row_words() - 1, y);
+}
+
+// The word to the right of (wx, y). Only its bottom bit is used, as the
+// neighbor of the last cell of the word at (wx, y).
+fn word_east(wx: i32, y: i32) -> u32 {
+    if (wx < row_words() - 1) {
+        return word(wx + 1, y);
+    }
+    if (params.boundary == 1u32) {
+        return 0u32;
+    }
+    if (params.boundary == 2u32) {
+        return word(wx, y) >> 31u32;
+    }
+    return word(0, y);
+}
+
+// Each cell's left neighbor, and its right neighbor, in the row of the word.
+fn west(wx: i32, y: i32) -> u32 {
+    return (word(wx, y) << 1u32) | (word_west(wx, y) >> 31u32);
+}
+
+fn east(wx: i32, y: i32) -> u32 {
+    return (word(wx, y) >> 1u32) | (word_east(wx, y) << 31u32);
+}
+
+// The cells whose neighbor count is n, given the bits of the counts.
+fn count_is(n: u32, ones: u32, twos: u32, fours: u32, eights: u32) -> u32 {
+    var cells : u32 = ~0u32;
+    if ((n & 1u32) != 0u32) { cells = cells & ones; } else { cells = cells & ~ones; }
+    if ((n & 2u32) != 0u32) { cells = cells & twos; } else { cells = cells & ~twos; }
+    if ((n & 4u32) != 0u32) { cells = cells & fours; } else { cells = cells & ~fours; }
+    if ((n & 8u32) != 0u32) { cells = cells & eights; } else { cells = cells & ~eights; }
+    return cells;
+}
+
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn life([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let WX : i32 = i32(global_id.x);
+    let Y : i32 = i32(global_id.y);
+
+    if (WX >= row_words() || Y >= i32(params.height)) {
+        return;
+    }
+
+    // The eight neighbors of each cell in the word.
+    let n0 : u32 = west(WX, Y - 1);
+    let n1 : u32 = word(WX, Y - 1);
+    let n2 : u32 = east(WX, Y - 1);
+    let n3 : u32 = west(WX, Y);
+    let n4 : u32 = east(WX, Y);
+    let n5 : u32 = west(WX, Y + 1);
+    let n6 : u32 = word(WX, Y + 1);
+    let n7 : u32 = east(WX, Y + 1);
+
+    // Sum them in a tree of full and half adders. Each "a" is a sum bit,
+    // and each "c" is the carry out of the same adder.
+    let a0 : u32 = n0 ^ n1 ^ n2;
+    let c0 : u32 = (n0 & n1) | (n2 & (n0 ^ n1));
+    let a1 : u32 = n3 ^ n4 ^ n5;
+    let c1 : u32 = (n3 & n4) | (n5 & (n3 ^ n4));
+    let a2 : u32 = n6 ^ n7;
+    let c2 : u32 = n6 & n7;
+
+    let ones : u32 = a0 ^ a1 ^ a2;
+    let c3 : u32 = (a0 & a1) | (a2 & (a0 ^ a1));
+
+    let a4 : u32 = c0 ^ c1 ^ c2;
+    let c4 : u32 = (c0 & c1) | (c2 & (c0 ^ c1));
+    let twos : u32 = a4 ^ c3;
+    let c5 : u32 = a4 & c3;
+
+    let fours : u32 = c4 ^ c5;
+    let eights : u32 = c4 & c5;
+
+    // Apply the rule to all the cells at once.
+    var born : u32 = 0u32;
+    var survive : u32 = 0u32;
+    for (var n : u32 = 0u32; n <= 8u32; n = n + 1u32) {
+        let cells : u32 = count_is(n, ones, twos, fours, eights);
+        if ((params.birth & (1u32 << n)) != 0u32) {
+            born = born | cells;
+        }
+        if ((params.survival & (1u32 << n)) != 0u32) {
+            survive = survive | cells;
+        }
+    }
+
+    let alive : u32 = word(WX, Y);
+    cellDst.words[Y * row_words() + WX] = (alive & survive) | (~alive & born);
+}
+
+// Write the current cells into the texture, for rendering. This is a
+// separate pass so that stepping a packed grid doesn't pay for writing a
+// whole float per cell.
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn show([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let WX : i32 = i32(global_id.x);
+    let Y : i32 = i32(global_id.y);
+
+    if (WX >= row_words() || Y >= i32(params.height)) {
+        return;
+    }
+
+    let w : u32 = word(WX, Y);
+    for (var bit : u32 = 0u32; bit < 32u32; bit = bit + 1u32) {
+        textureStore(texture,
+            vec2<i32>(WX * 32 + i32(bit), Y),
+            vec4<f32>(f32((w >> bit) & 1u32), 0.0, 0.0, 1.0));
+    }
+}
@@ -4,6 +4,8 @@ use std::mem;
 
 use crate::{
     dimensions::Dimensions,
+    rule::{Neighborhood, Rule},
+    topology::{Boundary, Topology},
 };
 
 // ---------------------------------------------------------------------------
@@ -11,16 +13,130 @@ use crate::{
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
-struct Params {
-    width : u32,
-    height : u32,
-    threshold : f32,
+pub struct Params {
+    pub width : u32,
+    pub height : u32,
+    pub threshold : f32,
+    pub birth : u32,
+    pub survival : u32,
+    pub states : u32,
+    pub decay : f32,
+    pub birth_min : u32,
+    pub birth_max : u32,
+    pub survival_min : u32,
+    pub survival_max : u32,
+    pub radius : u32,
+    pub von_neumann : u32,
+    pub include_center : u32,
+    pub lenia : u32,
+    pub mu : f32,
+    pub sigma : f32,
+    pub dt : f32,
+    pub boundary : u32,
+    pub flip_x : u32,
+    pub flip_y : u32,
+    pub shift_x : i32,
+    pub shift_y : i32,
+}
+
+impl Params {
+    pub fn new(
+        dimensions: Dimensions,
+        threshold: f32,
+        decay: f32,
+        rule: &Rule,
+        topology: &Topology,
+    ) -> Self {
+        let mut params = Params {
+            width: dimensions.width(),
+            height: dimensions.height(),
+            threshold,
+            decay,
+            ..Params::zeroed()
+        };
+        params.set_topology(topology);
+        params.set_rule(rule);
+        params
+    }
+
+    // Set the fields which describe the rule, clearing any left over from
+    // the last rule.
+    pub fn set_rule(
+        &mut self,
+        rule: &Rule,
+    ) {
+        let mut params = Params {
+            width: self.width,
+            height: self.height,
+            threshold: self.threshold,
+            decay: self.decay,
+            boundary: self.boundary,
+            flip_x: self.flip_x,
+            flip_y: self.flip_y,
+            shift_x: self.shift_x,
+            shift_y: self.shift_y,
+            ..Params::zeroed()
+        };
+        match rule {
+            Rule::Life(rule) => {
+                let (birth_min, birth_max) = rule.birth().range();
+                let (survival_min, survival_max) = rule.survival().range();
+                params.birth = rule.birth().mask();
+                params.survival = rule.survival().mask();
+                params.states = rule.states();
+                params.birth_min = birth_min;
+                params.birth_max = birth_max;
+                params.survival_min = survival_min;
+                params.survival_max = survival_max;
+                params.radius = rule.radius();
+                params.von_neumann =
+                    (rule.neighborhood() == Neighborhood::VonNeumann) as u32;
+                params.include_center = rule.include_center() as u32;
+            }
+            Rule::Lenia(rule) => {
+                params.lenia = 1;
+                params.states = 2;
+                params.radius = rule.radius();
+                params.mu = rule.mu();
+                params.sigma = rule.sigma();
+                params.dt = rule.dt();
+            }
+        }
+        *self = params;
+    }
+
+    // Set the fields which describe what lies beyond the edges of the grid.
+    // The size that the topology gives, if any, is ignored.
+    pub fn set_topology(
+        &mut self,
+        topology: &Topology,
+    ) {
+        let (flip_x, flip_y) = topology.flips();
+        let (shift_x, shift_y) = topology.shifts();
+        self.boundary = topology.boundary() as u32;
+        self.flip_x = flip_x as u32;
+        self.flip_y = flip_y as u32;
+        self.shift_x = shift_x;
+        self.shift_y = shift_y;
+    }
+
+    // Change how cells beyond the edges are found, keeping any flips and
+    // shifts, which apply when the edges wrap around.
+    pub fn set_boundary(
+        &mut self,
+        boundary: Boundary,
+    ) {
+        self.boundary = boundary as u32;
+    }
 }
 
 // ---------------------------------------------------------------------------
 
 pub struct LifeParams {
+    params : Params,
     param_buf : wgpu::Buffer,
+    // Whether params has changed since it was last uploaded.
+    dirty : bool,
 }
 
 impl LifeParams {
@@ -28,12 +144,11 @@ impl LifeParams {
         device: &wgpu::Device,
         dimensions: Dimensions,
         threshold: f32,
+        decay: f32,
+        rule: &Rule,
+        topology: &Topology,
     ) -> Self {
-        let params = Params {
-            width: dimensions.width(),
-            height: dimensions.height(),
-            threshold,
-        };
+        let params = Params::new(dimensions, threshold, decay, rule, topology);
         let param_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("parameters buffer"),
             contents: bytemuck::bytes_of(&params),
@@ -41,7 +156,54 @@ impl LifeParams {
         });
 
         LifeParams {
+            params,
             param_buf,
+            dirty: false,
+        }
+    }
+
+    // The parameters, as the shaders see them.
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    // Change the parameters. The change reaches the GPU at the next upload().
+    pub fn set(
+        &mut self,
+        params: Params,
+    ) {
+        assert_eq!((params.width, params.height), (self.params.width, self.params.height));
+        self.params = params;
+        self.dirty = true;
+    }
+
+    // Change the size of the grid. This puts the parameters in a new
+    // buffer, so anything bound to the old one needs binding to this.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        dimensions: Dimensions,
+    ) {
+        self.params.width = dimensions.width();
+        self.params.height = dimensions.height();
+        self.param_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("parameters buffer"),
+            contents: bytemuck::bytes_of(&self.params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        self.dirty = false;
+    }
+
+    // Send any changes to the GPU, where they're seen by every pass after
+    // this, whether it's computing or rendering, since they all share the
+    // buffer.
+    pub fn upload(
+        &mut self,
+        queue: &wgpu::Queue,
+    ) {
+        if self.dirty {
+            queue.write_buffer(&self.param_buf, 0, bytemuck::bytes_of(&self.params));
+            self.dirty = false;
         }
     }
 
@@ -59,3 +221,24 @@ impl LifeParams {
         }
     }
 }
+
+#[test]
+fn change_params() {
+    let dim = Dimensions::new(64, 64);
+    let klein = Topology::parse("K64*,64").unwrap();
+    let bosco = Rule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+
+    // Changing the rule leaves nothing behind from the old one.
+    let mut params = Params::new(dim, 0.7, 0.01, &bosco, &klein);
+    params.set_rule(&Rule::conway());
+    let conway = Params::new(dim, 0.7, 0.01, &Rule::conway(), &klein);
+    assert_eq!(bytemuck::bytes_of(&params), bytemuck::bytes_of(&conway));
+
+    // The twist comes back when the edges wrap around again.
+    params.set_boundary(Boundary::Dead);
+    assert_eq!((params.boundary, params.flip_x), (1, 1));
+    params.set_boundary(Boundary::Wrap);
+    assert_eq!(bytemuck::bytes_of(&params), bytemuck::bytes_of(&conway));
+    params.set_topology(&Topology::torus());
+    assert_eq!((params.boundary, params.flip_x), (0, 0));
+}
@@ -0,0 +1,260 @@
+// A version of the life() pass in life.wgsl for an unbounded plane, which
+// is held as a sparse set of square tiles of $TILE_SIZE x $TILE_SIZE cells.
+// Each tile is kept in a slot of the cell buffers, and the tile table says,
+// for each slot, which slots hold the tile and its eight neighbors. Cells
+// in tiles which aren't held anywhere are dead. See sparse.rs for how tiles
+// come and go.
+
+[[block]]
+struct LifeParams {
+    width : u32;
+    height : u32;
+    threshold : f32;
+    birth : u32;
+    survival : u32;
+    states : u32;
+    decay : f32;
+    birth_min : u32;
+    birth_max : u32;
+    survival_min : u32;
+    survival_max : u32;
+    radius : u32;
+    von_neumann : u32;
+    include_center : u32;
+    lenia : u32;
+    mu : f32;
+    sigma : f32;
+    dt : f32;
+    boundary : u32;
+    flip_x : u32;
+    flip_y : u32;
+    shift_x : i32;
+    shift_y : i32;
+};
+
+[[block]]
+struct Cells {
+    cells : array<f32>;
+};
+
+[[block]]
+struct Slots {
+    slots : array<i32>;
+};
+
+[[block]]
+struct Flags {
+    flags : array<u32>;
+};
+
+[[group(0), binding(0)]] var<uniform> params : LifeParams;
+[[group(0), binding(1)]] var<storage> cellSrc : [[access(read)]] Cells;
+[[group(0), binding(2)]] var<storage> cellDst : [[access(write)]] Cells;
+[[group(0), binding(3)]] var texture : [[access(write)]] texture_storage_2d<r32float>;
+// Nine entries for each slot: the slots holding the tiles at (dx, dy) from
+// its tile, at index (dy + 1) * 3 + (dx + 1), or -1 if there are none. The
+// middle entry is -1 if the slot is free.
+[[group(0), binding(4)]] var<storage> tiles : [[access(read)]] Slots;
+// Which tiles each slot's tile needs around it, as set by summarize().
+[[group(0), binding(5)]] var<storage> flags : [[access(write)]] Flags;
+// The tiles which the texture shows: the position of the texture's top
+// left cell within the first tile, the number of columns and rows of
+// tiles, and then the slot of each tile, row by row, or -1 for none.
+[[group(0), binding(6)]] var<storage> view : [[access(read)]] Slots;
+
+// The value of the cell at (x, y) from the top left of the slot's tile,
+// where (x, y) may be up to a tile beyond its edges.
+fn cell(slot: i32, x: i32, y: i32) -> f32 {
+    let T : i32 = $TILE_SIZE;
+    var dx : i32 = 0;
+    var dy : i32 = 0;
+    if (x < 0) {
+        dx = -1;
+    } else {
+        if (x >= T) {
+            dx = 1;
+        }
+    }
+    if (y < 0) {
+        dy = -1;
+    } else {
+        if (y >= T) {
+            dy = 1;
+        }
+    }
+
+    let s : i32 = tiles.slots[slot * 9 + (dy + 1) * 3 + (dx + 1)];
+    if (s < 0) {
+        return 0.0;
+    }
+    return cellSrc.cells[s * T * T + (y - dy * T) * T + (x - dx * T)];
+}
+
+// 1 if the cell at (x, y) is alive, 0 otherwise.
+fn alive(slot: i32, x: i32, y: i32) -> u32 {
+    if (cell(slot, x, y) > params.threshold) {
+        return 1u32;
+    }
+    return 0u32;
+}
+
+// Whether count is in the set given by a bitmask and an inclusive range.
+fn in_set(count: u32, mask: u32, lo: u32, hi: u32) -> bool {
+    if (count < 32u32 && (mask & (1u32 << count)) != 0u32) {
+        return true;
+    }
+    return count >= lo && count <= hi;
+}
+
+// The next value of a cell, as in life.wgsl.
+fn transition(ov: f32, count: u32) -> f32 {
+    let thresh : f32 = params.threshold;
+    var nv : f32;
+
+    if (ov < 0.0) {
+        nv = min(ov + 1.0, 0.0);
+    } else {
+        if (ov > thresh) {
+            if (in_set(count, params.survival,
+                    params.survival_min, params.survival_max)) {
+                if (ov - params.decay > thresh) {
+                    nv = ov - params.decay;
+                } else {
+                    nv = ov;
+                }
+            } else {
+                nv = -f32(params.states - 2u32);
+            }
+        } else {
+            if (in_set(count, params.birth,
+                    params.birth_min, params.birth_max)) {
+                nv = 1.0;
+            } else {
+                nv = 0.0;
+            }
+        }
+    }
+    return nv;
+}
+
+// Step each cell of each tile which is held in a slot. The z dimension of
+// the dispatch is the slot.
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn life([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let T : i32 = $TILE_SIZE;
+    let X : i32 = i32(global_id.x);
+    let Y : i32 = i32(global_id.y);
+    let slot : i32 = i32(global_id.z);
+
+    if (tiles.slots[slot * 9 + 4] < 0) {
+        return;
+    }
+
+    var count : u32 = 0u32;
+    for (var dy : i32 = -1; dy <= 1; dy = dy + 1) {
+        for (var dx : i32 = -1; dx <= 1; dx = dx + 1) {
+            if (params.von_neumann == 0u32 || dx == 0 || dy == 0) {
+                count = count + alive(slot, X + dx, Y + dy);
+            }
+        }
+    }
+    if (params.include_center == 0u32) {
+        count = count - alive(slot, X, Y);
+    }
+
+    cellDst.cells[slot * T * T + Y * T + X] = transition(cell(slot, X, Y), count);
+}
+
+// For each row of a tile, bit 0 says whether any cell in it isn't dead,
+// and bits 1 and 2 say the same of its first and last $TILE_MARGIN cells.
+var<workgroup> rows : array<u32, $TILE_SIZE>;
+
+// Work out which tiles each slot's tile needs around it: bit n of its flags
+// is set if the tile at (dx, dy) from it is needed, where n is
+// (dy + 1) * 3 + (dx + 1). The middle bit says that the tile has any cells
+// that aren't dead, and the others say that it has such cells within
+// $TILE_MARGIN cells of the neighbor, which could reach it before the
+// tiles are next checked. Each workgroup looks after one slot, with an
+// invocation for each row.
+[[stage(compute), workgroup_size($TILE_SIZE)]]
+fn summarize(
+    [[builtin(local_invocation_id)]] local_id: vec3<u32>,
+    [[builtin(workgroup_id)]] group_id: vec3<u32>,
+) {
+    let T : i32 = $TILE_SIZE;
+    let M : i32 = $TILE_MARGIN;
+    let slot : i32 = i32(group_id.x);
+    let Y : i32 = i32(local_id.x);
+
+    var bits : u32 = 0u32;
+    if (tiles.slots[slot * 9 + 4] >= 0) {
+        for (var x : i32 = 0; x < T; x = x + 1) {
+            if (cellSrc.cells[slot * T * T + Y * T + x] != 0.0) {
+                bits = bits | 1u32;
+                if (x < M) {
+                    bits = bits | 2u32;
+                }
+                if (x >= T - M) {
+                    bits = bits | 4u32;
+                }
+            }
+        }
+    }
+    rows[Y] = bits;
+    workgroupBarrier();
+
+    if (Y != 0) {
+        return;
+    }
+
+    var needs : u32 = 0u32;
+    for (var y : i32 = 0; y < T; y = y + 1) {
+        // The rows of tiles, above and below, which this row can reach.
+        var reach : u32 = 2u32;
+        if (y < M) {
+            reach = reach | 1u32;
+        }
+        if (y >= T - M) {
+            reach = reach | 4u32;
+        }
+        for (var dy : u32 = 0u32; dy < 3u32; dy = dy + 1u32) {
+            if ((reach & (1u32 << dy)) != 0u32) {
+                if ((rows[y] & 1u32) != 0u32) {
+                    needs = needs | (1u32 << (dy * 3u32 + 1u32));
+                }
+                if ((rows[y] & 2u32) != 0u32) {
+                    needs = needs | (1u32 << (dy * 3u32));
+                }
+                if ((rows[y] & 4u32) != 0u32) {
+                    needs = needs | (1u32 << (dy * 3u32 + 2u32));
+                }
+            }
+        }
+    }
+    flags.flags[slot] = needs;
+}
+
+// Write the cells that the texture shows into it.
+[[stage(compute), workgroup_size($WORKGROUP_WIDTH, $WORKGROUP_HEIGHT)]]
+fn show([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let T : i32 = $TILE_SIZE;
+    let X : i32 = i32(global_id.x);
+    let Y : i32 = i32(global_id.y);
+
+    if (X >= i32(params.width) || Y >= i32(params.height)) {
+        return;
+    }
+
+    let x : i32 = view.slots[0] + X;
+    let y : i32 = view.slots[1] + Y;
+    let s : i32 = view.slots[4 + (y / T) * view.slots[2] + x / T];
+    var v : f32 = 0.0;
+    if (s >= 0) {
+        v = cellSrc.cells[s * T * T + (y % T) * T + x % T];
+    }
+
+    textureStore(texture,
+        vec2<i32>(X, Y),
+        // all channels other than the first are ignored
+        vec4<f32>(v, 0.0, 0.0, 1.0));
+}
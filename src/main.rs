@@ -1,104 +1,391 @@
 // #[path = "../framework.rs"]
 mod framework;
 
-use rand::{
-    distributions::{Distribution, Uniform},
-    SeedableRng,
-};
-
+mod camera;
+mod clock;
+mod config;
+mod cpu_life;
 mod debug_buffer;
 mod dimensions;
 mod directions;
+mod edit;
+mod hashlife;
+mod history;
 mod life;
 mod life_params;
+mod pattern;
 mod renderer;
+mod rule;
+mod selection;
+mod simulation;
+mod sparse;
 mod texture;
+mod topology;
+
+use std::{mem, path::PathBuf, time::Instant};
+
+use cgmath::{Point2, Vector2};
+use winit::event::{ModifiersState, VirtualKeyCode};
 
 use crate::{
+    camera::Camera,
+    clock::Clock,
+    config::{Config, Resize},
+    cpu_life::CpuLife,
     debug_buffer::DebugBuffer,
     dimensions::Dimensions,
-    life::Life,
-    life_params::LifeParams,
+    edit::{CellEdit, EditLog, Revision, Stroke},
+    hashlife::Hashlife,
+    history::History,
+    life::{Kernel, Life, LifeGrid},
+    life_params::Params,
+    pattern::{self, Format},
     renderer::Renderer,
-    texture::Texture,
+    rule::Rule,
+    selection::{Clip, Rect, SystemClipboard},
+    simulation::{self, Simulation},
+    sparse::SparseLife,
+    topology::Boundary,
 };
 
+/// How much the threshold and decay change for each key press, and the
+/// most that the decay can be turned up to.
+const THRESHOLD_STEP: f32 = 0.05;
+const DECAY_STEP: f32 = 0.005;
+const MAX_DECAY: f32 = 0.1;
+
+/// How much each click of the mouse wheel, or press of Page Up, zooms in;
+/// how many pixels a trackpad scrolls for the same; and how many pixels
+/// each press of an arrow key moves the view.
+const ZOOM_STEP: f32 = 1.25;
+const PIXELS_PER_LINE: f32 = 40.0;
+const PAN_STEP: f32 = 64.0;
+
 // ---------------------------------------------------------------------------
 
 /// LifeProg struct holds all of the state used by the program.
 struct LifeProg {
-    life: Life,
+    sim: Simulation,
     renderer: Renderer,
     debug_buffer: DebugBuffer<f32>,
+    save_requested: bool,
+    engine: Engine,
+    camera: Camera,
+    grid_lines: bool,
+    /// On an unbounded plane, the cell of the plane at the grid's first
+    /// cell, and how far the view has been panned past that, in cells.
+    plane_view: (i64, i64),
+    plane_pan: Vector2<f32>,
+    /// Where the mouse is in the window, whether it's dragging the view,
+    /// and which modifier keys are held down.
+    cursor: Point2<f32>,
+    dragging: bool,
+    modifiers: ModifiersState,
+    /// The cells being drawn with the mouse, if any, and the changes made
+    /// to cells which haven't been written to the grid yet.
+    stroke: Option<Stroke>,
+    edits: Vec<CellEdit>,
+    /// The changes made by hand, whether the next edits written to the grid
+    /// start a new change, and the undos and redos to do in the next frame.
+    edit_log: EditLog,
+    new_change: bool,
+    revisions: Vec<Revision>,
+    /// The rectangle of cells selected, if any, the cell that a selection
+    /// being dragged out started from, and whether the selection is to be
+    /// copied in the next frame.
+    selection: Option<Rect>,
+    selecting: Option<(i64, i64)>,
+    copy_requested: bool,
+    /// The cells last copied, the cells being pasted, which follow the
+    /// cursor until they're put down, and whether the renderer has been
+    /// given those to show yet.
+    clipboard: Option<Clip>,
+    system_clipboard: SystemClipboard,
+    pasting: Option<Clip>,
+    paste_changed: bool,
+    clock: Clock,
+    /// Earlier generations of the grid, if they're kept, and the generation
+    /// to wind back to in the next frame, if any.
+    history: Option<History>,
+    rewind_to: Option<usize>,
+    /// The config the grid was set up with, which says what happens to it
+    /// when the window is resized.
+    config: Config,
+}
+
+/// What steps the grid: the GPU's Life in the Simulation, or one of the
+/// engines for an unbounded plane, which take over from it after setting up
+/// the initial grid.
+enum Engine {
+    Grid,
+    /// Hashlife on the CPU, stepping 2^K generations at a time.
+    Hashlife(Hashlife, u32),
+    /// Sparse tiles on the GPU.
+    Sparse(SparseLife),
+}
+
+impl Engine {
+    /// Set up the engine that the config asks for, starting from the grid
+    /// in the Simulation, and run it through the warm-up generations.
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sim: &Simulation,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let warmup = config.warmup().into();
+        if let Some(step) = config.hashlife {
+            let mut hashlife = Hashlife::new(sim.dimensions, sim.params.params())?;
+            hashlife.import(sim.life.export(device, queue));
+            hashlife.advance(warmup);
+            Ok(Engine::Hashlife(hashlife, step))
+        } else if config.unbounded {
+            let mut sparse = SparseLife::new(device, &sim.params, &sim.texture, config.tiles)?;
+            sparse.import(queue, sim.life.export(device, queue), sim.dimensions);
+            sparse.run(device, queue, warmup);
+            Ok(Engine::Sparse(sparse))
+        } else {
+            Ok(Engine::Grid)
+        }
+    }
+
+    /// Check that the engine can run with new parameters, and have it use
+    /// them from here on.
+    fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        match self {
+            Engine::Grid => Ok(()),
+            Engine::Hashlife(hashlife, _) => hashlife.set_params(params),
+            Engine::Sparse(sparse) => sparse.set_params(params),
+        }
+    }
+}
+
+/// The config for the Simulation: as given, unless another engine is going
+/// to take over, in which case that engine runs the warm-up instead.
+fn grid_config(config: &Config) -> Config {
+    if config.hashlife.is_some() || config.unbounded {
+        Config { warmup: Some(0), ..config.clone() }
+    } else {
+        config.clone()
+    }
+}
+
+/// A history of the given grid, in as many MiB of GPU memory as the config
+/// allows, or None if that's too little for a keyframe, or too much to
+/// count in bytes.
+fn new_history(
+    life: &Life,
+    config: &Config,
+) -> Option<History> {
+    match config.history.checked_mul(1 << 20) {
+        Some(budget) => History::new(life, budget),
+        None => {
+            println!("Can't keep {} MiB of history: that's more than can be addressed",
+                config.history);
+            None
+        }
+    }
 }
 
 impl framework::Example for LifeProg {
+    type Config = Config;
+
     /// Construct the initial instance of the LifeProg struct.
     fn init(
         sc_desc: &wgpu::SwapChainDescriptor,
         _adapter: &wgpu::Adapter,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+        config: &Config,
     ) -> Self {
-        let dim = Dimensions::new(sc_desc.width, sc_desc.height);
-        let ncells = dim.area();
-
-        // Parameters for the game, shared between compute and fragment shaders.
-        let params = LifeParams::new(&device, dim, 0.70);
+        let window = Dimensions::new(sc_desc.width, sc_desc.height);
+        let dim = config.dimensions(Some(window)).unwrap_or_else(fail);
 
-        // Create the texture that's shared between compute and fragment shaders.
-        let texture = Texture::new(&device, dim, wgpu::TextureFormat::R32Float);
-
-        // Initialize the life algorithm.
-        let mut life = Life::new(&device, dim, &params, &texture);
-
-        // Set the initial state for all cells in the life grid.
-        life.import(&device, &queue, {
-            let mut cell_data : Vec<f32> = Vec::new();
-            let mut rng = rand::rngs::StdRng::seed_from_u64(42);
-            let unif = Uniform::new_inclusive(0.0, 1.0);
-            for _ in 0..ncells {
-                cell_data.push(unif.sample(&mut rng));
-            }
-            cell_data
-        });
-
-        // Step the algorithm a few times, so the initial image looks Life-like.
-        let mut command_encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: None
-            });
-        for _ in 0..100 {
-            life.step(&mut command_encoder);
-        }
-        queue.submit(Some(command_encoder.finish()));
+        // Set up the grid, and run it through the warm-up generations.
+        let sim = Simulation::new(device, queue, dim, &grid_config(config))
+            .unwrap_or_else(fail);
+        let engine = Engine::new(device, queue, &sim, config).unwrap_or_else(fail);
 
         // Initialize the vertex and fragment shaders.
-        let renderer = Renderer::new(&sc_desc, &device, &params, &texture);
+        let renderer = Renderer::new(&sc_desc, &device, &sim.params, &sim.texture);
 
         // Create a buffer to allow snooping on the generated data.
-        let debug_buffer = DebugBuffer::new(&device, ncells);
+        let debug_buffer = DebugBuffer::new(&device, dim.area());
+
+        // Keep earlier generations, for winding back. Engines for unbounded
+        // planes keep their own cells, so there's no history of those.
+        let history = match engine {
+            Engine::Grid => new_history(&sim.life, config),
+            _ => None,
+        };
 
         LifeProg {
-            life,
+            sim,
             renderer,
             debug_buffer,
+            save_requested: false,
+            engine,
+            camera: Camera::new(dim, window),
+            grid_lines: false,
+            plane_view: (0, 0),
+            plane_pan: Vector2::new(0.0, 0.0),
+            cursor: Point2::new(0.0, 0.0),
+            dragging: false,
+            modifiers: ModifiersState::empty(),
+            stroke: None,
+            edits: Vec::new(),
+            edit_log: EditLog::new(),
+            new_change: true,
+            revisions: Vec::new(),
+            selection: None,
+            selecting: None,
+            copy_requested: false,
+            clipboard: None,
+            system_clipboard: SystemClipboard::new(),
+            pasting: None,
+            paste_changed: false,
+            clock: Clock::new(),
+            history,
+            rewind_to: None,
+            config: config.clone(),
         }
     }
 
     /// update is called for any WindowEvent not handled by the framework
-    fn update(&mut self, _event: winit::event::WindowEvent) {
-        // empty
+    fn update(&mut self, event: winit::event::WindowEvent) {
+        use winit::event::{
+            ElementState, KeyboardInput, MouseButton, MouseScrollDelta, WindowEvent,
+        };
+
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+                ..
+            } => self.key_pressed(key),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+
+            // Dragging with the left button draws live cells, or selects
+            // them with Shift held down, with the right button erases them,
+            // and with the middle button pans the view.
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = Point2::new(position.x as f32, position.y as f32);
+                if self.dragging {
+                    self.pan(cursor - self.cursor);
+                }
+                if let Some(stroke) = &mut self.stroke {
+                    self.edits.extend(stroke.to(self.camera.grid_point(cursor)));
+                }
+                self.cursor = cursor;
+                if let Some(start) = self.selecting {
+                    self.selection = Some(Rect::spanning(start, self.cursor_cell()));
+                }
+            }
+            WindowEvent::MouseInput { state, button: MouseButton::Middle, .. } =>
+                self.dragging = state == ElementState::Pressed,
+            WindowEvent::MouseInput { state, button, .. } => {
+                let value = match button {
+                    MouseButton::Left => 1.0,
+                    MouseButton::Right => 0.0,
+                    _ => return,
+                };
+                self.stroke = None;
+                self.selecting = None;
+                if state == ElementState::Released {
+                    return;
+                }
+                if !matches!(self.engine, Engine::Grid) {
+                    println!("Cells can't be drawn on an unbounded plane");
+                    return;
+                }
+
+                // While cells are being pasted, the left button puts them
+                // down, as a change of their own, and the right button
+                // gives up on them.
+                if let Some(clip) = self.pasting.take() {
+                    if button == MouseButton::Left {
+                        self.edits.extend(clip.paste(self.cursor_cell(), self.sim.dimensions));
+                        self.new_change = true;
+                    }
+                    return;
+                }
+                if button == MouseButton::Left && self.modifiers.shift() {
+                    let cell = self.cursor_cell();
+                    self.selecting = Some(cell);
+                    self.selection = Some(Rect::spanning(cell, cell));
+                    return;
+                }
+
+                // Each stroke is a change of its own, to be undone at once.
+                let mut stroke = Stroke::new(self.sim.dimensions, value);
+                self.edits.extend(stroke.to(self.camera.grid_point(self.cursor)));
+                self.stroke = Some(stroke);
+                self.new_change = true;
+            }
+
+            // The wheel zooms in and out around the cursor.
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) =>
+                        position.y as f32 / PIXELS_PER_LINE,
+                };
+                self.camera.zoom(ZOOM_STEP.powf(lines), self.cursor);
+            }
+            _ => {}
+        }
     }
 
     /// resize is called on WindowEvent::Resized events
     fn resize(
         &mut self,
-        _sc_desc: &wgpu::SwapChainDescriptor,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
     ) {
-        // empty
+        // A minimized window has no size, and nothing to show.
+        let window = Dimensions::new(sc_desc.width, sc_desc.height);
+        if window.area() == 0 {
+            return;
+        }
+        self.camera.resize(window);
+
+        // A grid which took its size from the window may follow it, unless
+        // it's been handed over to an engine for an unbounded plane.
+        if self.config.resize == Resize::View || !self.config.sized_by_window()
+            || !matches!(self.engine, Engine::Grid) {
+            return;
+        }
+        let dim = self.config.dimensions(Some(window)).unwrap_or_else(fail);
+        if dim == self.sim.dimensions {
+            return;
+        }
+        if let Err(e) = self.sim.resize(device, queue, dim, self.config.resize) {
+            println!("Keeping the grid at {}x{}, since {}",
+                self.sim.dimensions.width(), self.sim.dimensions.height(), e);
+            return;
+        }
+        self.renderer.set_sources(device, &self.sim.params, &self.sim.texture);
+        self.debug_buffer = DebugBuffer::new(device, dim.area());
+        self.camera = Camera::new(dim, window);
+
+        // Any drawing that was under way was on the old grid, and the
+        // history and the edit log are of that grid.
+        self.stroke = None;
+        self.edits.clear();
+        self.edit_log.clear();
+        self.revisions.clear();
+        self.selection = None;
+        self.selecting = None;
+        self.pasting = None;
+        self.history = new_history(&self.sim.life, &self.config);
+        self.rewind_to = None;
     }
 
     /// render is called to generate each new frame
@@ -111,6 +398,17 @@ impl framework::Example for LifeProg {
     ) {
         let debug = false;
 
+        // Any parameters changed since the last frame take effect now, as
+        // does any change in the view, or in what's being pasted.
+        self.sim.params.upload(queue);
+        if mem::take(&mut self.paste_changed) {
+            if let Some(clip) = &self.pasting {
+                self.renderer.set_paste(device, &self.sim.params, &self.sim.texture, clip);
+            }
+        }
+        let paste = self.pasting.as_ref().map(|clip| clip.rect(self.cursor_cell()));
+        self.renderer.set_view(queue, &self.camera, self.grid_lines, self.selection, paste);
+
         let mut command_encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: None
@@ -118,11 +416,77 @@ impl framework::Example for LifeProg {
 
         if debug {
             self.debug_buffer.enqueue_copyin(&mut command_encoder,
-                 &self.life.src_buf());
+                 &self.sim.life.src_buf());
         }
 
-        // Run the life algorithm one step.
-        self.life.step(&mut command_encoder);
+        // Wind back, if that's been asked for, and then copy the selection
+        // and change any cells drawn since the last frame, before it's
+        // stepped. The winding back is done at once, since copying and
+        // changing cells go straight to the queue. The edits are logged
+        // along with the values they replaced, so that they can be undone;
+        // then any undos and redos asked for are done, in order.
+        if let (Some(generation), Some(history)) = (self.rewind_to.take(), &self.history) {
+            let mut rewind_encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: None
+                });
+            match history.rewind(&mut rewind_encoder, &mut self.sim.life, generation) {
+                Ok(()) => println!("Wound back to generation {}", generation),
+                Err(e) => println!("Can't wind back to generation {}: {}", generation, e),
+            }
+            queue.submit(Some(rewind_encoder.finish()));
+        }
+        if mem::take(&mut self.copy_requested) {
+            self.copy(device, queue);
+        }
+        let mut edited = false;
+        if !self.edits.is_empty() {
+            let undo = self.sim.life.write_cells(device, queue, &self.edits);
+            self.edit_log.record(mem::take(&mut self.edits), undo,
+                mem::replace(&mut self.new_change, false));
+            edited = true;
+        }
+        for revision in self.revisions.drain(..) {
+            match self.edit_log.revise(revision) {
+                Some(edits) => {
+                    self.sim.life.write_cells(device, queue, &edits);
+                    edited = true;
+                }
+                None => println!("Nothing to {}",
+                    if revision == Revision::Undo { "undo" } else { "redo" }),
+            }
+            // A stroke carried on after this is a new change.
+            self.new_change = true;
+        }
+        if edited {
+            if let Some(history) = &mut self.history {
+                history.edited(device, &mut command_encoder, &self.sim.life);
+            }
+        }
+
+        // Run the life algorithm as many steps as the clock says are due
+        // since the last frame, which may be none.
+        let steps = self.clock.tick(Instant::now());
+        match &mut self.engine {
+            Engine::Grid => {
+                self.sim.life.step_n(&mut command_encoder, steps);
+                self.sim.life.show(&mut command_encoder);
+                if let Some(history) = &mut self.history {
+                    history.record(device, &mut command_encoder, &self.sim.life);
+                }
+            }
+            Engine::Hashlife(hashlife, step) => {
+                for _ in 0..steps {
+                    hashlife.step_pow2(*step);
+                }
+                hashlife.show(queue, &self.sim.texture, self.plane_view.0, self.plane_view.1);
+            }
+            Engine::Sparse(sparse) => {
+                sparse.run(device, queue, steps.into());
+                sparse.set_view(queue, self.plane_view.0, self.plane_view.1);
+                sparse.show(&mut command_encoder);
+            }
+        }
 
         // Render the life cells into actual pixels, and display them.
         self.renderer.render(&mut command_encoder, &view);
@@ -130,16 +494,535 @@ impl framework::Example for LifeProg {
         queue.submit(Some(command_encoder.finish()));
 
         if debug {
-            println!("Life data at step {}:", self.life.frame_num());
+            println!("Life data at step {}:", self.sim.life.frame_num());
             self.debug_buffer.display(device);
             println!("");
         }
+
+        if self.save_requested {
+            self.save_requested = false;
+            self.save(device, queue);
+        }
+    }
+}
+
+impl LifeProg {
+    /// Act on a key being pressed.
+    fn key_pressed(
+        &mut self,
+        key: VirtualKeyCode,
+    ) {
+        match key {
+            // The grid is saved on the next render, since that's when we
+            // have access to the device.
+            VirtualKeyCode::S => self.save_requested = true,
+
+            VirtualKeyCode::LBracket => self.adjust(|p|
+                p.threshold = (p.threshold - THRESHOLD_STEP).max(THRESHOLD_STEP)),
+            VirtualKeyCode::RBracket => self.adjust(|p|
+                p.threshold = (p.threshold + THRESHOLD_STEP).min(1.0 - THRESHOLD_STEP)),
+            VirtualKeyCode::Semicolon => self.adjust(|p|
+                p.decay = (p.decay - DECAY_STEP).max(0.0)),
+            VirtualKeyCode::Apostrophe => self.adjust(|p|
+                p.decay = (p.decay + DECAY_STEP).min(MAX_DECAY)),
+            VirtualKeyCode::B => self.adjust(|p| p.set_boundary(match p.boundary {
+                b if b == Boundary::Wrap as u32 => Boundary::Dead,
+                b if b == Boundary::Dead as u32 => Boundary::Mirror,
+                _ => Boundary::Wrap,
+            })),
+
+            // The arrow keys move the view, and Page Up and Page Down zoom
+            // in and out around the middle of the window.
+            VirtualKeyCode::Left => self.pan(Vector2::new(PAN_STEP, 0.0)),
+            VirtualKeyCode::Right => self.pan(Vector2::new(-PAN_STEP, 0.0)),
+            VirtualKeyCode::Up => self.pan(Vector2::new(0.0, PAN_STEP)),
+            VirtualKeyCode::Down => self.pan(Vector2::new(0.0, -PAN_STEP)),
+            VirtualKeyCode::PageUp => self.camera.zoom(ZOOM_STEP, self.camera.middle()),
+            VirtualKeyCode::PageDown => self.camera.zoom(1.0 / ZOOM_STEP, self.camera.middle()),
+            VirtualKeyCode::Home => {
+                self.camera.reset();
+                self.plane_view = (0, 0);
+                self.plane_pan = Vector2::new(0.0, 0.0);
+            }
+            VirtualKeyCode::G => self.grid_lines = !self.grid_lines,
+
+            // Ctrl+Z undoes the last change made by hand, and Ctrl+Y or
+            // Ctrl+Shift+Z redoes it, whether or not the grid is running.
+            VirtualKeyCode::Z if self.modifiers.ctrl() => self.revisions.push(
+                if self.modifiers.shift() { Revision::Redo } else { Revision::Undo }),
+            VirtualKeyCode::Y if self.modifiers.ctrl() => self.revisions.push(Revision::Redo),
+
+            // Ctrl+A selects the whole grid, and Ctrl+Shift+A nothing.
+            // Ctrl+C copies the selection, Ctrl+X cuts it, and Ctrl+V
+            // pastes what was last copied, here or in another program.
+            VirtualKeyCode::A if self.modifiers.ctrl() => {
+                self.selection = if self.modifiers.shift() || !matches!(self.engine, Engine::Grid) {
+                    None
+                } else {
+                    Some(Rect::grid(self.sim.dimensions))
+                };
+            }
+            VirtualKeyCode::C if self.modifiers.ctrl() =>
+                self.copy_requested = self.selection.is_some(),
+            VirtualKeyCode::X if self.modifiers.ctrl() => {
+                self.copy_requested = self.selection.is_some();
+                self.fill_selection(|n| vec![0.0; n]);
+            }
+            VirtualKeyCode::V if self.modifiers.ctrl() => self.paste(),
+
+            // Cells being pasted can be turned a quarter turn clockwise
+            // with T, and flipped with H and V.
+            VirtualKeyCode::T => self.transform_paste(Clip::rotate),
+            VirtualKeyCode::H => self.transform_paste(Clip::flip_x),
+            VirtualKeyCode::V => self.transform_paste(Clip::flip_y),
+
+            // Delete clears the selection, F fills it with live cells, and
+            // D fills it with a random soup, as dense as --density says.
+            VirtualKeyCode::Delete | VirtualKeyCode::Back =>
+                self.fill_selection(|n| vec![0.0; n]),
+            VirtualKeyCode::F => self.fill_selection(|n| vec![1.0; n]),
+            VirtualKeyCode::D => {
+                let (density, threshold) = (self.config.density, self.sim.threshold);
+                self.fill_selection(|n| simulation::soup(Dimensions::new(n as u32, 1),
+                    rand::random(), density, threshold));
+            }
+
+            // Space pauses and resumes, N takes a single step, and the plus
+            // and minus keys change the speed.
+            VirtualKeyCode::Space => {
+                self.clock.toggle_pause();
+                println!("Speed: {}", self.clock);
+            }
+            VirtualKeyCode::N => self.clock.single_step(1),
+
+            // Comma and period pause, and go back or forward by as many
+            // generations as a 60th of a second takes at the current speed.
+            // With Shift, they go back or forward to the next keyframe in
+            // the history instead.
+            VirtualKeyCode::Comma if self.modifiers.shift() => self.scrub(-1),
+            VirtualKeyCode::Period if self.modifiers.shift() => self.scrub(1),
+            VirtualKeyCode::Comma => self.rewind(),
+            VirtualKeyCode::Period => self.clock.single_step(self.clock.jump_steps()),
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                self.clock.faster();
+                println!("Speed: {}", self.clock);
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                self.clock.slower();
+                println!("Speed: {}", self.clock);
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the view by the given number of pixels, as Camera::pan() does.
+    /// On an unbounded plane, the grid is moved across the plane instead, a
+    /// whole number of cells at a time, so that whatever travels across it
+    /// can be followed as far as it goes.
+    fn pan(
+        &mut self,
+        pixels: Vector2<f32>,
+    ) {
+        if matches!(self.engine, Engine::Grid) {
+            self.camera.pan(pixels);
+            return;
+        }
+        self.plane_pan += pixels / self.camera.scale();
+        let cells = Vector2::new(self.plane_pan.x.trunc(), self.plane_pan.y.trunc());
+        self.plane_pan -= cells;
+        self.plane_view.0 -= cells.x as i64;
+        self.plane_view.1 += cells.y as i64;
+    }
+
+    /// The cell under the cursor, or the nearest one on the grid.
+    fn cursor_cell(&self) -> (i64, i64) {
+        let point = self.camera.grid_point(self.cursor);
+        selection::grid_cell((point.x, point.y), self.sim.dimensions)
+    }
+
+    /// Set every cell of the selection, if there is one, to the values that
+    /// the given function makes for that many cells, as a change of its own.
+    fn fill_selection(
+        &mut self,
+        values: impl FnOnce(usize) -> Vec<f32>,
+    ) {
+        if let Some(rect) = self.selection {
+            let cells = rect.cells(self.sim.dimensions);
+            let values = values(cells.len());
+            self.edits.extend(cells.into_iter().zip(values));
+            self.new_change = true;
+        }
+    }
+
+    /// Copy the selection into the clipboard, and onto the system clipboard
+    /// as RLE text.
+    fn copy(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let rect = match self.selection {
+            Some(rect) => rect,
+            None => return,
+        };
+        let values = self.sim.life.read_cells(device, queue, &rect.cells(self.sim.dimensions));
+        let clip = Clip::new(rect.width, rect.height, values);
+        let mut pattern = clip.to_pattern(self.sim.threshold);
+        pattern.set_rule(Some(self.sim.rule.to_string()));
+        match self.system_clipboard.set(pattern::write(&pattern, Format::Rle)) {
+            Ok(()) => println!("Copied {}x{} cells", rect.width, rect.height),
+            Err(e) => println!("Copied {}x{} cells, but not to the system clipboard: {}",
+                rect.width, rect.height, e),
+        }
+        self.clipboard = Some(clip);
     }
+
+    /// Start pasting the cells last copied: a pattern copied in another
+    /// program since anything was copied here, or else the clipboard.
+    fn paste(&mut self) {
+        if !matches!(self.engine, Engine::Grid) {
+            println!("Cells can't be pasted onto an unbounded plane");
+            return;
+        }
+        if let Some(text) = self.system_clipboard.take_new() {
+            if let Some(format) = Format::detect(&text) {
+                match pattern::read(&text, format) {
+                    Ok(pattern) if pattern.cells().is_empty() =>
+                        println!("The pattern on the system clipboard has no live cells"),
+                    Ok(pattern) if pattern.width() > self.sim.dimensions.width()
+                        || pattern.height() > self.sim.dimensions.height() =>
+                        println!("The {}x{} pattern on the system clipboard is too large \
+                            to paste", pattern.width(), pattern.height()),
+                    Ok(pattern) => self.clipboard = Some(Clip::from_pattern(&pattern)),
+                    Err(e) => println!("Can't paste the system clipboard: {}", e),
+                }
+            }
+        }
+        match &self.clipboard {
+            Some(clip) => {
+                self.pasting = Some(clip.clone());
+                self.paste_changed = true;
+            }
+            None => println!("Nothing to paste"),
+        }
+    }
+
+    /// Change the cells being pasted, if there are any.
+    fn transform_paste(
+        &mut self,
+        transform: fn(&Clip) -> Clip,
+    ) {
+        if let Some(clip) = &self.pasting {
+            self.pasting = Some(transform(clip));
+            self.paste_changed = true;
+        }
+    }
+
+    /// Pause, and go back by a 60th of a second's worth of generations in
+    /// the next frame, if the history goes back that far.
+    fn rewind(&mut self) {
+        if self.history.is_none() {
+            println!("No history is kept, so the grid can't be wound back");
+            return;
+        }
+        self.clock.pause();
+        let current = self.rewind_to.unwrap_or_else(|| self.sim.life.frame_num());
+        self.rewind_to = Some(current.saturating_sub(self.clock.jump_steps() as usize));
+    }
+
+    /// Pause, and go to the generation of the keyframe the given number of
+    /// keyframes later in the history, or earlier if it's negative, in the
+    /// next frame.
+    fn scrub(
+        &mut self,
+        keyframes: i32,
+    ) {
+        let history = match &self.history {
+            Some(history) => history,
+            None => {
+                println!("No history is kept, so the grid can't be scrubbed through");
+                return;
+            }
+        };
+        self.clock.pause();
+        let current = self.rewind_to.unwrap_or_else(|| self.sim.life.frame_num());
+        match history.keyframe_from(current, keyframes) {
+            Some(generation) => self.rewind_to = Some(generation),
+            None => println!("There's no keyframe {} generation {}",
+                if keyframes < 0 { "before" } else { "after" }, current),
+        }
+    }
+
+    /// Change the parameters in some way, if every part of the program can
+    /// run with the changed ones, and say what they are now.
+    fn adjust(
+        &mut self,
+        change: impl FnOnce(&mut Params),
+    ) {
+        let old = *self.sim.params.params();
+        let mut params = old;
+        change(&mut params);
+        let result = self.engine.set_params(&params)
+            .and_then(|()| self.sim.set_params(params));
+        if let Err(e) = result {
+            // The engine is left as it was, since the old parameters worked.
+            let _ = self.engine.set_params(&old);
+            println!("Can't change the parameters: {}", e);
+            return;
+        }
+        // Replaying the history would now give different cells.
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        println!("Threshold {:.2}, decay {:.3}, {} boundary",
+            params.threshold, params.decay,
+            match params.boundary {
+                b if b == Boundary::Wrap as u32 => "wrapping",
+                b if b == Boundary::Dead as u32 => "dead",
+                _ => "mirrored",
+            });
+    }
+
+    /// Save the live cells of the grid to an RLE file.
+    fn save(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let (frame_num, grid) = match &self.engine {
+            Engine::Grid => (self.sim.life.frame_num() as u64, None),
+            Engine::Hashlife(hashlife, _) => (hashlife.generation(), Some(hashlife.export())),
+            Engine::Sparse(sparse) =>
+                (sparse.frame_num() as u64, Some(sparse.export(device, queue))),
+        };
+        let path = PathBuf::from(format!("life-{}.rle", frame_num));
+        let saved = match grid {
+            Some(grid) => simulation::save_grid(&path, &grid,
+                self.sim.dimensions, self.sim.threshold, &self.sim.rule),
+            None => self.sim.save(device, queue, &path),
+        };
+        match saved {
+            Ok(()) => println!("Saved generation {} to {}", frame_num, path.display()),
+            Err(e) => println!("Failed to save: {}", e),
+        }
+    }
+}
+
+/// Run the given number of generations without a window, and save the
+/// final grid. If there's no usable GPU, the CPU implementation is used.
+#[cfg(not(target_arch = "wasm32"))]
+fn headless(
+    config: &Config,
+    generations: u64,
+) -> Result<(), String> {
+    let dim = config.dimensions(None)?;
+    if config.verify && (config.hashlife.is_some() || config.unbounded) {
+        return Err("a run on an unbounded plane can't be verified, since it has no edges".to_string());
+    }
+    let (grid, rule, frame_num) = if config.hashlife.is_some() {
+        headless_hashlife(config, dim, generations)?
+    } else {
+        match framework::headless::<LifeProg>(config.adapter.as_deref()) {
+            Ok((_adapter, device, queue)) if config.unbounded =>
+                headless_sparse(config, dim, generations, &device, &queue)?,
+            Ok((_adapter, device, queue)) =>
+                headless_gpu(config, dim, generations, &device, &queue)?,
+            Err(e) if !config.verify && !config.unbounded => {
+                println!("{}; running on the CPU instead", e);
+                headless_cpu(config, dim, generations)?
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    let path = config.output.clone().unwrap_or_else(||
+        PathBuf::from(format!("life-{}.rle", frame_num)));
+    simulation::save_grid(&path, &grid, dim, simulation::THRESHOLD, &rule)?;
+    println!("Saved generation {} to {}", frame_num, path.display());
+    Ok(())
+}
+
+/// Run a headless simulation on the GPU, returning the final grid, the rule
+/// and the generation number.
+#[cfg(not(target_arch = "wasm32"))]
+fn headless_gpu(
+    config: &Config,
+    dim: Dimensions,
+    generations: u64,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<(Vec<f32>, Rule, u64), String> {
+    let mut sim = Simulation::new(device, queue, dim, config)?;
+    let start_grid = if config.verify {
+        Some(sim.life.export(device, queue))
+    } else {
+        None
+    };
+
+    let grid = run_timed(&mut sim.life, &(device, queue), dim, generations);
+
+    // Run the CPU implementation from the same starting point, and check
+    // that it gets the same result.
+    if let Some(start_grid) = start_grid {
+        let mut cpu = CpuLife::new(dim, sim.params.params());
+        cpu.import(&(), start_grid);
+        cpu.run(&(), generations);
+        cpu.verify(&grid)?;
+        println!("The CPU reference implementation agrees");
+    }
+
+    Ok((grid, sim.rule, sim.life.frame_num() as u64))
+}
+
+/// Run a headless simulation on an unbounded plane of sparse tiles,
+/// returning the part of the plane where the grid started, the rule and the
+/// generation number.
+#[cfg(not(target_arch = "wasm32"))]
+fn headless_sparse(
+    config: &Config,
+    dim: Dimensions,
+    generations: u64,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<(Vec<f32>, Rule, u64), String> {
+    let sim = Simulation::new(device, queue, dim, &grid_config(config))?;
+    let mut sparse = SparseLife::new(device, &sim.params, &sim.texture, config.tiles)?;
+    sparse.import(queue, sim.life.export(device, queue), dim);
+    sparse.run(device, queue, config.warmup().into());
+
+    let start = Instant::now();
+    sparse.run(device, queue, generations);
+    report_speed(dim, generations, start);
+    println!("{} tiles are in use", sparse.tile_count());
+
+    Ok((sparse.export(device, queue), sim.rule, sparse.frame_num() as u64))
+}
+
+/// Run a headless simulation on the CPU, returning the final grid, the rule
+/// and the generation number.
+#[cfg(not(target_arch = "wasm32"))]
+fn headless_cpu(
+    config: &Config,
+    dim: Dimensions,
+    generations: u64,
+) -> Result<(Vec<f32>, Rule, u64), String> {
+    let (rule, cells) = simulation::initial_state(dim, config)?;
+    let params = Params::new(dim, simulation::THRESHOLD, simulation::DECAY,
+        &rule, &config.topology);
+    let mut life = CpuLife::new(dim, &params);
+    life.import(&(), cells);
+    life.run(&(), config.warmup().into());
+
+    let grid = run_timed(&mut life, &(), dim, generations);
+    Ok((grid, rule, life.frame_num() as u64))
+}
+
+/// Run the given number of generations of a grid, on whatever it runs on,
+/// report how fast they went, and return the final grid.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_timed<C>(
+    life: &mut impl LifeGrid<C>,
+    context: &C,
+    dim: Dimensions,
+    generations: u64,
+) -> Vec<f32> {
+    let start = Instant::now();
+    life.run(context, generations);
+    report_speed(dim, generations, start);
+    life.export(context)
+}
+
+/// Run a headless simulation with Hashlife, returning the part of the plane
+/// where the grid started, the rule and the generation number.
+#[cfg(not(target_arch = "wasm32"))]
+fn headless_hashlife(
+    config: &Config,
+    dim: Dimensions,
+    generations: u64,
+) -> Result<(Vec<f32>, Rule, u64), String> {
+    let (rule, cells) = simulation::initial_state(dim, config)?;
+    let params = Params::new(dim, simulation::THRESHOLD, 0.0, &rule, &config.topology);
+    let mut life = Hashlife::new(dim, &params)?;
+    life.import(cells);
+    life.advance(config.warmup().into());
+
+    let start = Instant::now();
+    life.advance(generations);
+    report_speed(dim, generations, start);
+    println!("Generation {} has {} live cells", life.generation(), life.population());
+
+    Ok((life.export(), rule, life.generation()))
+}
+
+/// Time each way of stepping the grid which works for its rule, running the
+/// given number of generations with each.
+#[cfg(not(target_arch = "wasm32"))]
+fn bench(
+    config: &Config,
+    generations: u32,
+) -> Result<(), String> {
+    let dim = config.dimensions(None)?;
+    let (_adapter, device, queue) = framework::headless::<LifeProg>(config.adapter.as_deref())?;
+
+    let variants = [
+        ("direct", false, Kernel::Direct),
+        ("tiled", false, Kernel::Tiled),
+        ("blocked", false, Kernel::Blocked),
+        ("packed", true, Kernel::Direct),
+    ];
+    for &(name, packed, kernel) in &variants {
+        let config = Config { packed, kernel, ..config.clone() };
+        let mut sim = match Simulation::new(&device, &queue, dim, &config) {
+            Ok(sim) if sim.life.kernel() == kernel => sim,
+            Ok(_) => {
+                println!("{:>8}: skipped, since it doesn't work with this rule and topology", name);
+                continue;
+            }
+            Err(e) => {
+                println!("{:>8}: skipped, since {}", name, e);
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        sim.run(&device, &queue, generations.into());
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("{:>8}: {:.1} generations/s, {:.3} billion cells/s", name,
+            generations as f64 / elapsed,
+            generations as f64 * dim.area() as f64 / elapsed / 1e9);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn report_speed(
+    dim: Dimensions,
+    generations: u64,
+    start: Instant,
+) {
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("Ran {} generations of a {}x{} grid in {:.3}s ({:.1} generations/s)",
+        generations, dim.width(), dim.height(), elapsed, generations as f64 / elapsed);
+}
+
+/// Report a problem with the configuration, and exit.
+fn fail<T>(message: String) -> T {
+    eprintln!("{}", message);
+    std::process::exit(1);
 }
 
 /// run example
 fn main() {
-    framework::run::<LifeProg>("life");
+    let config = Config::from_args(std::env::args().skip(1)).unwrap_or_else(fail);
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(generations) = config.bench {
+        return bench(&config, generations).unwrap_or_else(fail);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(generations) = config.headless {
+        return headless(&config, generations).unwrap_or_else(fail);
+    }
+    framework::run::<LifeProg>("life", config);
 }
 
 #[test]
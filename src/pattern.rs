@@ -0,0 +1,293 @@
+// Patterns: sets of live cells which can be placed into the Life grid, or
+// taken from it, and read from or written to files in the usual formats.
+
+pub mod life105;
+pub mod life106;
+pub mod macrocell;
+pub mod plaintext;
+pub mod rle;
+
+use std::{fs, path::Path};
+
+use crate::{
+    dimensions::Dimensions,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Rle,
+    Plaintext,
+    Life105,
+    Life106,
+    Macrocell,
+}
+
+impl Format {
+    // Guess the format of a pattern file from its extension. Life 1.05 and
+    // 1.06 files share the ".lif" extension; 1.06 is the more common today.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rle" => Some(Format::Rle),
+            "cells" => Some(Format::Plaintext),
+            "lif" | "life" => Some(Format::Life106),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    // Recognize the format of a pattern from its first line.
+    pub fn detect(text: &str) -> Option<Format> {
+        let first = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+        if first.starts_with("#Life 1.05") {
+            Some(Format::Life105)
+        } else if first.starts_with("#Life 1.06") {
+            Some(Format::Life106)
+        } else if first.starts_with("[M2]") {
+            Some(Format::Macrocell)
+        } else if first.starts_with('!') || first.chars().all(|c| ".O".contains(c)) {
+            Some(Format::Plaintext)
+        } else {
+            // RLE files may start with comments; skip past them.
+            let header = text.lines().map(str::trim)
+                .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+            if header.starts_with('x') && header.contains('=') {
+                Some(Format::Rle)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+pub fn read(
+    text: &str,
+    format: Format,
+) -> Result<Pattern, String> {
+    match format {
+        Format::Rle => rle::read(text),
+        Format::Plaintext => plaintext::read(text),
+        Format::Life105 => life105::read(text),
+        Format::Life106 => life106::read(text),
+        Format::Macrocell => macrocell::read(text, None),
+    }
+}
+
+pub fn write(
+    pattern: &Pattern,
+    format: Format,
+) -> String {
+    match format {
+        Format::Rle => rle::write(pattern),
+        Format::Plaintext => plaintext::write(pattern),
+        Format::Life105 => life105::write(pattern),
+        Format::Life106 => life106::write(pattern),
+        Format::Macrocell => macrocell::write(pattern),
+    }
+}
+
+// Load a pattern from a file, recognizing its format from its contents or
+// failing that from its extension. Macrocell patterns can describe far more
+// cells than would fit in memory, so they are checked against the size of
+// the grid before being expanded.
+pub fn load(
+    path: &Path,
+    dimensions: Dimensions,
+) -> Result<Pattern, String> {
+    let text = fs::read_to_string(path).map_err(|e|
+        format!("couldn't read {}: {}", path.display(), e))?;
+    let format = Format::detect(&text)
+        .or_else(|| Format::from_path(path))
+        .ok_or_else(|| format!("couldn't tell what format {} is in", path.display()))?;
+    let pattern = match format {
+        Format::Macrocell => macrocell::read(&text, Some(dimensions)),
+        _ => read(&text, format),
+    };
+    pattern.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Save a pattern to a file, in the format given by its extension, or RLE if
+// the extension isn't recognized.
+pub fn save(
+    path: &Path,
+    pattern: &Pattern,
+) -> Result<(), String> {
+    let format = Format::from_path(path).unwrap_or(Format::Rle);
+    fs::write(path, write(pattern, format)).map_err(|e|
+        format!("couldn't write {}: {}", path.display(), e))
+}
+
+pub struct Pattern {
+    width: u32,
+    height: u32,
+    // The live cells, relative to the top left corner of the pattern.
+    cells: Vec<(u32, u32)>,
+    rule: Option<String>,
+}
+
+impl Pattern {
+    pub fn new(
+        width: u32,
+        height: u32,
+        cells: Vec<(u32, u32)>,
+    ) -> Self {
+        debug_assert!(cells.iter().all(|&(x, y)| x < width && y < height));
+        Pattern {
+            width,
+            height,
+            cells,
+            rule: None,
+        }
+    }
+
+    // Make a pattern from the coordinates of its live cells, which may be
+    // anywhere, including at negative coordinates.
+    pub fn from_coords(coords: &[(i64, i64)]) -> Result<Self, String> {
+        if coords.is_empty() {
+            return Ok(Pattern::new(0, 0, Vec::new()));
+        }
+
+        let x0 = coords.iter().map(|&(x, _)| x).min().unwrap();
+        let x1 = coords.iter().map(|&(x, _)| x).max().unwrap();
+        let y0 = coords.iter().map(|&(_, y)| y).min().unwrap();
+        let y1 = coords.iter().map(|&(_, y)| y).max().unwrap();
+        if x1 - x0 >= u32::MAX as i64 || y1 - y0 >= u32::MAX as i64 {
+            return Err("pattern is too large".to_string());
+        }
+
+        let mut cells: Vec<(u32, u32)> = coords.iter()
+            .map(|&(x, y)| ((x - x0) as u32, (y - y0) as u32))
+            .collect();
+        cells.sort_by_key(|&(x, y)| (y, x));
+        cells.dedup();
+        Ok(Pattern::new((x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32, cells))
+    }
+
+    // Take the live cells from a grid of cell values, such as Life::export()
+    // returns. The pattern is cropped to the bounding box of the live cells.
+    // The grid's first row is its bottom one, as it's shown, while a
+    // pattern's is its top one, so the rows are taken in reverse order.
+    pub fn from_grid(
+        grid: &[f32],
+        dimensions: Dimensions,
+        threshold: f32,
+    ) -> Self {
+        assert_eq!(grid.len(), dimensions.area());
+
+        let width = dimensions.width() as usize;
+        let live: Vec<(u32, u32)> = grid.chunks(width)
+            .rev()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter()
+                .enumerate()
+                .filter(|(_, &v)| v > threshold)
+                .map(move |(x, _)| (x as u32, y as u32)))
+            .collect();
+        if live.is_empty() {
+            return Pattern::new(0, 0, live);
+        }
+
+        let x0 = live.iter().map(|&(x, _)| x).min().unwrap();
+        let x1 = live.iter().map(|&(x, _)| x).max().unwrap();
+        let y0 = live[0].1;
+        let y1 = live[live.len() - 1].1;
+        let cells = live.into_iter().map(|(x, y)| (x - x0, y - y0)).collect();
+        Pattern::new(x1 - x0 + 1, y1 - y0 + 1, cells)
+    }
+
+    // Place the pattern into an empty grid of cell values, suitable for
+    // passing to Life::import(), with its top left corner at the given offset
+    // from the top left corner of the grid. The pattern's rows go down the
+    // grid as it's shown, so its first row is near the grid's last.
+    pub fn to_grid(
+        &self,
+        dimensions: Dimensions,
+        offset: (u32, u32),
+    ) -> Result<Vec<f32>, String> {
+        if offset.0 as u64 + self.width as u64 > dimensions.width() as u64
+            || offset.1 as u64 + self.height as u64 > dimensions.height() as u64 {
+            return Err(format!(
+                "a {}x{} pattern at ({}, {}) doesn't fit in a {}x{} grid",
+                self.width, self.height, offset.0, offset.1,
+                dimensions.width(), dimensions.height()));
+        }
+
+        let mut grid = vec![0.0; dimensions.area()];
+        for &(x, y) in &self.cells {
+            let x = (offset.0 + x) as usize;
+            let y = (dimensions.height() - 1 - offset.1 - y) as usize;
+            grid[y * dimensions.width() as usize + x] = 1.0;
+        }
+        Ok(grid)
+    }
+
+    // The offset which places the pattern in the middle of the grid.
+    pub fn centered_in(
+        &self,
+        dimensions: Dimensions,
+    ) -> (u32, u32) {
+        (
+            dimensions.width().saturating_sub(self.width) / 2,
+            dimensions.height().saturating_sub(self.height) / 2,
+        )
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn cells(&self) -> &[(u32, u32)] {
+        &self.cells
+    }
+
+    pub fn rule(&self) -> Option<&str> {
+        self.rule.as_deref()
+    }
+
+    pub fn set_rule(
+        &mut self,
+        rule: Option<String>,
+    ) {
+        self.rule = rule;
+    }
+}
+
+#[test]
+fn grid_rows() {
+    // The top row of a pattern goes at the top of the grid as it's shown,
+    // which is the grid's last row, and comes back out as the top row.
+    let glider = Pattern::new(3, 3, vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    let dimensions = Dimensions::new(4, 3);
+    let grid = glider.to_grid(dimensions, (1, 0)).unwrap();
+    assert_eq!(grid, vec![
+        0.0, 1.0, 1.0, 1.0,
+        0.0, 0.0, 0.0, 1.0,
+        0.0, 0.0, 1.0, 0.0,
+    ]);
+    assert_eq!(Pattern::from_grid(&grid, dimensions, 0.5).cells(), glider.cells());
+}
+
+#[test]
+fn read_and_write_formats() {
+    let glider = Pattern::new(3, 3, vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    for &format in &[Format::Rle, Format::Plaintext, Format::Life105, Format::Life106,
+            Format::Macrocell] {
+        let text = write(&glider, format);
+        assert_eq!(Format::detect(&text), Some(format));
+        let copy = read(&text, format).unwrap();
+        assert_eq!((copy.width(), copy.height()), (3, 3));
+        assert_eq!(copy.cells(), glider.cells());
+    }
+
+    assert_eq!(Format::detect("#N Glider\nx = 3, y = 3\n"), Some(Format::Rle));
+    assert_eq!(Format::from_path(Path::new("gun.CELLS")), Some(Format::Plaintext));
+    let life106 = read("#Life 1.06\n-1 -1\n0 -1\n1 -1\n", Format::Life106).unwrap();
+    assert_eq!((life106.width(), life106.height()), (3, 1));
+    let life105 = read("#Life 1.05\n#R 23/36\n#P -1 -1\n.*\n#P 2 3\n*\n", Format::Life105).unwrap();
+    assert_eq!(life105.cells(), &[(0, 0), (2, 4)]);
+    assert_eq!(life105.rule(), Some("23/36"));
+}
@@ -0,0 +1,85 @@
+// The Life 1.05 format, which describes a pattern as blocks of cells.
+//
+// The first line is "#Life 1.05". A "#P x y" line gives the position of the
+// top left corner of the block of rows that follows it, relative to the
+// center of the pattern; in those rows, "." is a dead cell and "*" a live one.
+// "#N" says the rule is Conway's, and "#R 23/3" gives some other rule in
+// survival/birth form. Other lines starting with "#" are comments.
+
+use crate::{
+    pattern::Pattern,
+    rule::{Counts, Rule},
+};
+
+pub fn read(text: &str) -> Result<Pattern, String> {
+    let mut coords = Vec::new();
+    let mut rule = None;
+    let (mut x0, mut y) = (0i64, 0i64);
+
+    for line in text.lines().map(str::trim) {
+        if let Some(position) = line.strip_prefix("#P") {
+            let xy: Vec<i64> = position.split_whitespace()
+                .map(|n| n.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Life 1.05 pattern has an invalid line \"{}\"", line))?;
+            match xy.as_slice() {
+                &[px, py] => {
+                    x0 = px;
+                    y = py;
+                }
+                _ => return Err(format!("Life 1.05 pattern has an invalid line \"{}\"", line)),
+            }
+        } else if line.starts_with("#N") {
+            rule = Some(Rule::conway().to_string());
+        } else if let Some(sb) = line.strip_prefix("#R") {
+            rule = Some(sb.trim().to_string());
+        } else if line.starts_with('#') || line.is_empty() {
+            continue;
+        } else {
+            for (dx, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' => coords.push((x0 + dx as i64, y)),
+                    c => return Err(format!("Life 1.05 pattern has an unexpected character '{}'", c)),
+                }
+            }
+            y += 1;
+        }
+    }
+
+    let mut pattern = Pattern::from_coords(&coords)?;
+    pattern.set_rule(rule);
+    Ok(pattern)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = "#Life 1.05\n".to_string();
+
+    // Only two-state rules with the usual neighborhood can be described.
+    match pattern.rule().map(Rule::parse) {
+        None => text += "#N\n",
+        Some(Ok(rule)) if rule == Rule::conway() => text += "#N\n",
+        Some(Ok(Rule::Life(rule))) if rule.states() == 2 && rule.radius() == 1 => {
+            if let (Counts::Mask(birth), Counts::Mask(survival)) = (rule.birth(), rule.survival()) {
+                let counts = |mask: u32| -> String {
+                    (0..=8).filter(|n| mask & (1 << n) != 0).map(|n| n.to_string()).collect()
+                };
+                text += &format!("#R {}/{}\n", counts(survival), counts(birth));
+            }
+        }
+        Some(_) => {}
+    }
+
+    let width = pattern.width() as usize;
+    let mut rows = vec![vec!['.'; width]; pattern.height() as usize];
+    for &(x, y) in pattern.cells() {
+        rows[y as usize][x as usize] = '*';
+    }
+
+    text += &format!("#P {} {}\n", -(pattern.width() as i64 / 2), -(pattern.height() as i64 / 2));
+    for row in rows {
+        text.extend(row);
+        text += "\n";
+    }
+    text
+}
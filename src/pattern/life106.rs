@@ -0,0 +1,33 @@
+// The Life 1.06 format, which lists the coordinates of each live cell.
+//
+// The first line is "#Life 1.06", and each following line has the x and y
+// coordinates of one live cell, which may be negative.
+
+use crate::pattern::Pattern;
+
+pub fn read(text: &str) -> Result<Pattern, String> {
+    let mut coords = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let xy: Vec<i64> = line.split_whitespace()
+            .map(|n| n.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Life 1.06 pattern has an invalid line \"{}\"", line))?;
+        match xy.as_slice() {
+            &[x, y] => coords.push((x, y)),
+            _ => return Err(format!("Life 1.06 pattern has an invalid line \"{}\"", line)),
+        }
+    }
+    Pattern::from_coords(&coords)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = "#Life 1.06\n".to_string();
+    for &(x, y) in pattern.cells() {
+        text += &format!("{} {}\n", x, y);
+    }
+    text
+}
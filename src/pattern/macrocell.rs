@@ -0,0 +1,325 @@
+// Golly's Macrocell format, which stores a pattern as a quadtree so that
+// huge patterns with repeated structure stay small.
+//
+// The first line is "[M2]", optionally followed by the program that wrote
+// the file. "#R" lines give the rule, and other lines starting with "#" are
+// comments. Every other line defines a node of the tree, numbered from 1 in
+// the order they appear; node 0 is an empty node of any size. A node is
+// either an 8x8 leaf, written as rows of "." (dead) and "*" (live) cells
+// ended by "$", with trailing dead cells and rows left out; or a line
+// "k nw ne sw se", which is a 2^k by 2^k node built from four earlier nodes
+// of half its size. The last node is the whole pattern.
+//
+// Patterns are read without ever holding the whole quadtree expanded: the
+// bounding box of the live cells is found first, so a pattern that is too
+// large for the grid can be reported before any cells are expanded.
+
+use std::collections::HashMap;
+
+use crate::{
+    dimensions::Dimensions,
+    pattern::Pattern,
+};
+
+// The level of a leaf node, which is 2^3 = 8 cells on a side.
+const LEAF_LEVEL: u32 = 3;
+
+// The deepest tree we can handle while keeping coordinates in a u64.
+const MAX_LEVEL: u32 = 62;
+
+enum Node {
+    Empty,
+    // Bit x of row y is set if the cell at (x, y) is alive.
+    Leaf([u8; 8]),
+    Inner {
+        level: u32,
+        children: [usize; 4],
+    },
+}
+
+// The inclusive bounding box of a node's live cells: (x0, y0, x1, y1).
+type Bounds = (u64, u64, u64, u64);
+
+struct Tree {
+    nodes: Vec<Node>,
+    bounds: Vec<Option<Bounds>>,
+}
+
+impl Tree {
+    fn level(&self, node: usize) -> Option<u32> {
+        match self.nodes[node] {
+            Node::Empty => None,
+            Node::Leaf(_) => Some(LEAF_LEVEL),
+            Node::Inner { level, .. } => Some(level),
+        }
+    }
+
+    fn add(&mut self, node: Node) {
+        let bounds = match &node {
+            Node::Empty => None,
+            Node::Leaf(rows) => {
+                let live: Vec<(u64, u64)> = (0..8)
+                    .flat_map(|y| (0..8).map(move |x| (x, y)))
+                    .filter(|&(x, y)| rows[y as usize] & (1 << x) != 0)
+                    .collect();
+                live.iter().fold(None, |bounds, &(x, y)| Some(merge(bounds, (x, y, x, y))))
+            }
+            Node::Inner { level, children } => {
+                let half = 1u64 << (level - 1);
+                let offsets = [(0, 0), (half, 0), (0, half), (half, half)];
+                children.iter().zip(offsets.iter()).fold(None, |bounds, (&child, &(dx, dy))| {
+                    match self.bounds[child] {
+                        Some((x0, y0, x1, y1)) =>
+                            Some(merge(bounds, (x0 + dx, y0 + dy, x1 + dx, y1 + dy))),
+                        None => bounds,
+                    }
+                })
+            }
+        };
+        self.nodes.push(node);
+        self.bounds.push(bounds);
+    }
+
+    // Add the live cells of a node at (x, y) to cells, relative to origin.
+    fn expand(
+        &self,
+        node: usize,
+        x: u64,
+        y: u64,
+        origin: (u64, u64),
+        cells: &mut Vec<(u32, u32)>,
+    ) {
+        if self.bounds[node].is_none() {
+            return;
+        }
+        match &self.nodes[node] {
+            Node::Empty => {}
+            Node::Leaf(rows) => {
+                for (dy, row) in rows.iter().enumerate() {
+                    for dx in (0..8).filter(|dx| row & (1 << dx) != 0) {
+                        cells.push((
+                            (x + dx - origin.0) as u32,
+                            (y + dy as u64 - origin.1) as u32,
+                        ));
+                    }
+                }
+            }
+            Node::Inner { level, children } => {
+                let half = 1u64 << (level - 1);
+                self.expand(children[0], x, y, origin, cells);
+                self.expand(children[1], x + half, y, origin, cells);
+                self.expand(children[2], x, y + half, origin, cells);
+                self.expand(children[3], x + half, y + half, origin, cells);
+            }
+        }
+    }
+}
+
+fn merge(bounds: Option<Bounds>, other: Bounds) -> Bounds {
+    match bounds {
+        None => other,
+        Some((x0, y0, x1, y1)) =>
+            (x0.min(other.0), y0.min(other.1), x1.max(other.2), y1.max(other.3)),
+    }
+}
+
+// Read a Macrocell pattern. If a limit is given, a pattern whose live cells
+// won't fit within it is reported as an error.
+pub fn read(
+    text: &str,
+    limit: Option<Dimensions>,
+) -> Result<Pattern, String> {
+    let mut lines = text.lines().map(str::trim);
+    if !matches!(lines.next(), Some(line) if line.starts_with("[M2]")) {
+        return Err("Macrocell pattern doesn't start with [M2]".to_string());
+    }
+
+    let mut tree = Tree {
+        nodes: vec![Node::Empty],
+        bounds: vec![None],
+    };
+    let mut rule = None;
+    for line in lines {
+        if let Some(r) = line.strip_prefix("#R") {
+            rule = Some(r.trim().to_string());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let node = read_inner(&tree, line)?;
+            tree.add(node);
+        } else {
+            let node = read_leaf(line)?;
+            tree.add(node);
+        }
+    }
+
+    let root = tree.nodes.len() - 1;
+    let (x0, y0, x1, y1) = match tree.bounds[root] {
+        Some(bounds) => bounds,
+        None => return Ok(Pattern::new(0, 0, Vec::new())),
+    };
+    let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+    let fits = match limit {
+        Some(limit) => width <= limit.width() as u64 && height <= limit.height() as u64,
+        None => width <= u32::MAX as u64 && height <= u32::MAX as u64,
+    };
+    if !fits {
+        let (max_width, max_height) = limit.map_or((u32::MAX, u32::MAX),
+            |limit| (limit.width(), limit.height()));
+        return Err(format!(
+            "Macrocell pattern is {}x{} cells, which is larger than the {}x{} grid",
+            width, height, max_width, max_height));
+    }
+
+    let mut cells = Vec::new();
+    tree.expand(root, 0, 0, (x0, y0), &mut cells);
+    cells.sort_by_key(|&(x, y)| (y, x));
+
+    let mut pattern = Pattern::new(width as u32, height as u32, cells);
+    pattern.set_rule(rule);
+    Ok(pattern)
+}
+
+fn read_leaf(line: &str) -> Result<Node, String> {
+    let mut rows = [0u8; 8];
+    let (mut x, mut y) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '$' => {
+                x = 0;
+                y += 1;
+            }
+            '.' | '*' if x < 8 && y < 8 => {
+                if c == '*' {
+                    rows[y] |= 1 << x;
+                }
+                x += 1;
+            }
+            _ => return Err(format!("Macrocell pattern has an invalid leaf \"{}\"", line)),
+        }
+    }
+    Ok(Node::Leaf(rows))
+}
+
+fn read_inner(
+    tree: &Tree,
+    line: &str,
+) -> Result<Node, String> {
+    let invalid = || format!("Macrocell pattern has an invalid node \"{}\"", line);
+    let fields: Vec<usize> = line.split_whitespace()
+        .map(|n| n.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    if fields.len() != 5 {
+        return Err(invalid());
+    }
+
+    let level = fields[0] as u32;
+    if level <= LEAF_LEVEL {
+        // Nodes this small only appear in patterns for multi-state rules.
+        return Err("multi-state Macrocell patterns aren't supported".to_string());
+    }
+    if level > MAX_LEVEL {
+        return Err(format!("Macrocell pattern is too deep, at level {}", level));
+    }
+
+    let mut children = [0; 4];
+    for (child, &n) in children.iter_mut().zip(&fields[1..]) {
+        if n >= tree.nodes.len() {
+            return Err(invalid());
+        }
+        if let Some(child_level) = tree.level(n) {
+            if child_level != level - 1 {
+                return Err(invalid());
+            }
+        }
+        *child = n;
+    }
+    Ok(Node::Inner { level, children })
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = "[M2] (wgpu-life)\n".to_string();
+    if let Some(rule) = pattern.rule() {
+        text += &format!("#R {}\n", rule);
+    }
+
+    // Build the tree from the bottom up. Each level maps the position of a
+    // node within that level to its number; identical nodes share a number.
+    let mut leaves: HashMap<(u32, u32), [u8; 8]> = HashMap::new();
+    for &(x, y) in pattern.cells() {
+        leaves.entry((x / 8, y / 8)).or_insert([0; 8])[(y % 8) as usize] |= 1 << (x % 8);
+    }
+
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+    let mut lines: Vec<String> = Vec::new();
+    let mut number = |line: String, lines: &mut Vec<String>| -> usize {
+        *numbers.entry(line.clone()).or_insert_with(|| {
+            lines.push(line);
+            lines.len()
+        })
+    };
+
+    let mut level: HashMap<(u32, u32), usize> = HashMap::new();
+    let mut sorted: Vec<_> = leaves.into_iter().collect();
+    sorted.sort_by_key(|&((x, y), _)| (y, x));
+    for (pos, rows) in sorted {
+        let leaf: String = rows.iter().map(|row| {
+            let len = 8 - row.leading_zeros() as usize;
+            let mut cells: String = (0..len)
+                .map(|x| if row & (1 << x) != 0 { '*' } else { '.' })
+                .collect();
+            cells.push('$');
+            cells
+        }).collect();
+        level.insert(pos, number(leaf.trim_end_matches('$').to_string() + "$", &mut lines));
+    }
+
+    let size = pattern.width().max(pattern.height()).max(1);
+    let mut k = LEAF_LEVEL;
+    while (1u64 << k) < size as u64 || level.len() > 1 || k == LEAF_LEVEL {
+        let mut parents: HashMap<(u32, u32), [usize; 4]> = HashMap::new();
+        for (&(x, y), &n) in &level {
+            let quadrant = ((y % 2) * 2 + (x % 2)) as usize;
+            parents.entry((x / 2, y / 2)).or_insert([0; 4])[quadrant] = n;
+        }
+        k += 1;
+
+        let mut sorted: Vec<_> = parents.into_iter().collect();
+        sorted.sort_by_key(|&((x, y), _)| (y, x));
+        level = sorted.into_iter().map(|(pos, c)| {
+            (pos, number(format!("{} {} {} {} {}", k, c[0], c[1], c[2], c[3]), &mut lines))
+        }).collect();
+        if level.is_empty() {
+            break;
+        }
+    }
+
+    for line in lines {
+        text += &line;
+        text += "\n";
+    }
+    text
+}
+
+#[test]
+fn read_with_limit() {
+    // A glider in the bottom right corner of a 16x16 node.
+    let glider = "[M2] (golly 4.0)\n#R B3/S23\n$$$$$.*$..*$***$\n4 0 0 0 1\n";
+    let pattern = read(glider, Some(Dimensions::new(3, 3))).unwrap();
+    assert_eq!((pattern.width(), pattern.height()), (3, 3));
+    assert_eq!(pattern.cells(), &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    assert_eq!(pattern.rule(), Some("B3/S23"));
+
+    // Two gliders, far apart in a 2^40 by 2^40 node.
+    let mut far = "[M2]\n$$$$$.*$..*$***$\n".to_string();
+    for k in 4..=40 {
+        let n = k - 3;
+        far += &format!("{} {} 0 0 {}\n", k, if k == 4 { 0 } else { n }, n);
+    }
+    let err = read(&far, Some(Dimensions::new(1024, 1024))).err().unwrap();
+    assert!(err.contains("larger than the 1024x1024 grid"), "{}", err);
+
+    assert!(read(&write(&pattern), None).unwrap().cells() == pattern.cells());
+    assert!(read("[M2]\n1 0 1 0 1\n", None).is_err());
+}
@@ -0,0 +1,47 @@
+// The plaintext format used for ".cells" files on LifeWiki.
+//
+// Each line is a row of cells, with "." for a dead cell and "O" for a live
+// one. Lines starting with "!" are comments.
+
+use crate::pattern::Pattern;
+
+pub fn read(text: &str) -> Result<Pattern, String> {
+    let mut cells = Vec::new();
+    let mut width = 0;
+    let rows = text.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.starts_with('!'));
+
+    for (y, row) in rows.enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | 'o' | '*' => cells.push((x as u32, y as u32)),
+                c => return Err(format!("plaintext pattern has an unexpected character '{}'", c)),
+            }
+        }
+        width = width.max(row.chars().count() as u32);
+    }
+
+    // Trailing blank lines aren't part of the pattern.
+    let height = cells.last().map_or(0, |&(_, y)| y + 1);
+    Ok(Pattern::new(width, height, cells))
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let width = pattern.width() as usize;
+    let mut rows = vec![vec!['.'; width]; pattern.height() as usize];
+    for &(x, y) in pattern.cells() {
+        rows[y as usize][x as usize] = 'O';
+    }
+
+    let mut text = String::new();
+    if let Some(rule) = pattern.rule() {
+        text += &format!("!Rule: {}\n", rule);
+    }
+    for row in rows {
+        text.extend(row);
+        text += "\n";
+    }
+    text
+}
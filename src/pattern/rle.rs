@@ -0,0 +1,188 @@
+// The Run Length Encoded format, which is how most Life patterns are shared.
+//
+// A header line gives the size of the pattern and optionally its rule, as in
+// "x = 3, y = 3, rule = B3/S23". It is followed by the cells, row by row:
+// "b" is a dead cell, "o" is a live one, "$" ends a row and "!" ends the
+// pattern, and any of these may be preceded by a repeat count. Lines
+// starting with "#" are comments.
+//
+// Patterns for multi-state rules use "." for dead cells, "A" for live cells
+// and later letters up to "X" for states 2 to 24. States from 25 up take two
+// letters: one from "p" to "y", for each 24 states further on, then one
+// from "A" to "X", so "pA" is state 25 and "yO" is state 255. Those other
+// states are read as dead cells, since a Pattern only records which cells
+// are alive.
+
+use crate::pattern::Pattern;
+
+// Lines of cells are wrapped to be no longer than this.
+const MAX_LINE_LEN: usize = 70;
+
+pub fn read(text: &str) -> Result<Pattern, String> {
+    let mut lines = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header = lines.next().ok_or("RLE pattern has no header line")?;
+    let (mut width, mut height, mut rule) = (None, None, None);
+    for field in header.split(',') {
+        let mut parts = field.splitn(2, '=').map(str::trim);
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().ok_or_else(||
+            format!("RLE header field \"{}\" has no value", field.trim()))?;
+        let size = || value.parse::<u32>().map_err(|_|
+            format!("RLE header field \"{}\" isn't a valid size", field.trim()));
+        match key {
+            "x" => width = Some(size()?),
+            "y" => height = Some(size()?),
+            "rule" => rule = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    let (mut width, mut height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err("RLE header needs both x and y".to_string()),
+    };
+
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut count: Option<u32> = None;
+    let mut prefix: Option<char> = None;
+    'body: for line in lines {
+        for c in line.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                count = count.unwrap_or(0).checked_mul(10)
+                    .and_then(|n| n.checked_add(digit));
+                if count.is_none() {
+                    return Err("RLE pattern has a repeat count that is too large".to_string());
+                }
+                continue;
+            }
+
+            let n = count.take().unwrap_or(1);
+            let cell_state = match (prefix.take(), c) {
+                (None, '!') => break 'body,
+                (None, '$') => {
+                    y += n;
+                    x = 0;
+                    None
+                }
+                (None, 'b') | (None, '.') => Some(0),
+                (None, 'p'..='y') => {
+                    // The first letter of a two-letter state; the second
+                    // follows.
+                    prefix = Some(c);
+                    count = Some(n);
+                    continue;
+                }
+                (None, 'a'..='z') => Some(1),
+                (prefix, 'A'..='X') => Some(state(prefix, c)),
+                (None, c) if c.is_whitespace() => None,
+                (_, c) => return Err(format!("RLE pattern has an unexpected character '{}'", c)),
+            };
+            if let Some(cell_state) = cell_state {
+                if cell_state == 1 {
+                    cells.extend((x..x + n).map(|x| (x, y)));
+                }
+                x += n;
+            }
+
+            // Be forgiving of headers that understate the pattern's size.
+            width = width.max(x);
+            height = height.max(y + (x > 0) as u32);
+        }
+    }
+
+    let mut pattern = Pattern::new(width, height, cells);
+    pattern.set_rule(rule);
+    Ok(pattern)
+}
+
+// The state written as the given letter from "A" to "X", after the given
+// letter from "p" to "y", if there is one.
+fn state(
+    prefix: Option<char>,
+    letter: char,
+) -> u32 {
+    let page = prefix.map_or(0, |p| p as u32 - 'p' as u32 + 1);
+    24 * page + (letter as u32 - 'A' as u32 + 1)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = format!("x = {}, y = {}", pattern.width(), pattern.height());
+    if let Some(rule) = pattern.rule() {
+        text += &format!(", rule = {}", rule);
+    }
+    text += "\n";
+
+    let mut cells = pattern.cells().to_vec();
+    cells.sort_by_key(|&(x, y)| (y, x));
+
+    // Build up the runs of cells, then wrap them into lines.
+    let mut runs: Vec<String> = Vec::new();
+    let run = |n: u32, tag: char| if n == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", n, tag)
+    };
+    let (mut x, mut y) = (0, 0);
+    let mut i = 0;
+    while i < cells.len() {
+        let (cx, cy) = cells[i];
+        if cy > y {
+            runs.push(run(cy - y, '$'));
+            x = 0;
+            y = cy;
+        }
+        if cx > x {
+            runs.push(run(cx - x, 'b'));
+        }
+        let mut len = 1;
+        while i + len < cells.len() && cells[i + len] == (cx + len as u32, cy) {
+            len += 1;
+        }
+        runs.push(run(len as u32, 'o'));
+        x = cx + len as u32;
+        i += len;
+    }
+    runs.push("!".to_string());
+
+    let mut line = String::new();
+    for run in runs {
+        if line.len() + run.len() > MAX_LINE_LEN {
+            text += &line;
+            text += "\n";
+            line.clear();
+        }
+        line += &run;
+    }
+    text += &line;
+    text += "\n";
+    text
+}
+
+#[test]
+fn read_and_write() {
+    let glider = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n";
+    let pattern = read(glider).unwrap();
+    assert_eq!((pattern.width(), pattern.height()), (3, 3));
+    assert_eq!(pattern.cells(), &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    assert_eq!(pattern.rule(), Some("B3/S23"));
+    assert_eq!(write(&pattern), "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+
+    let brain = read("x = 4, y = 2, rule = B2/S/C3\n.AB$2A.pB!").unwrap();
+    assert_eq!(brain.cells(), &[(1, 0), (0, 1), (1, 1)]);
+
+    // States from 25 up take two letters, none of which are alive.
+    assert_eq!((state(None, 'A'), state(None, 'X')), (1, 24));
+    assert_eq!((state(Some('p'), 'A'), state(Some('y'), 'O')), (25, 255));
+    let generations = read("#C Generations\nx = 6, y = 2, rule = 23/3/256\n\
+        .ApA2qB$yOA.XAx\nA!").unwrap();
+    assert_eq!((generations.width(), generations.height()), (6, 2));
+    assert_eq!(generations.cells(), &[(1, 0), (1, 1), (4, 1)]);
+    assert!(read("x = 2, y = 1\npzA!").is_err());
+
+    assert!(read("x = 3\nbo!").is_err());
+    assert!(read("x = 3, y = 1\nbzo!").is_ok());
+    assert!(read("x = 3, y = 1\nb?o!").is_err());
+}
@@ -3,13 +3,29 @@ struct VertexOutput {
     [[location(0)]] tex_coord: vec2<f32>;
 };
 
+// Where the camera puts the quad that the texture is drawn on (see
+// Camera::transform()), how many pixels wide each cell is, whether to
+// draw lines between cells, and the cells which are selected and which are
+// being pasted over, each given by its first cell and the one past its last
+// (see Rect::corners()).
+[[block]]
+struct View {
+    transform : mat4x4<f32>;
+    scale : f32;
+    grid_lines : u32;
+    selection : vec4<i32>;
+    paste : vec4<i32>;
+};
+
+[[group(0), binding(2)]] var<uniform> view : View;
+
 [[stage(vertex)]]
 fn vs_main(
     [[location(0)]] position: vec4<f32>,
     [[location(1)]] tex_coord: vec2<f32>,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.position = position;
+    out.position = view.transform * position;
     out.tex_coord = tex_coord;
     return out;
 }
@@ -19,11 +35,43 @@ struct LifeParams {
     width : u32;
     height : u32;
     threshold : f32;
+    birth : u32;
+    survival : u32;
+    states : u32;
+    decay : f32;
+    birth_min : u32;
+    birth_max : u32;
+    survival_min : u32;
+    survival_max : u32;
+    radius : u32;
+    von_neumann : u32;
+    include_center : u32;
+    lenia : u32;
+    mu : f32;
+    sigma : f32;
+    dt : f32;
+    boundary : u32;
+    flip_x : u32;
+    flip_y : u32;
+    shift_x : i32;
+    shift_y : i32;
 };
 
 [[group(0), binding(0)]] var texture : [[access(read)]] texture_storage_2d<r32float>;
 [[group(0), binding(1)]] var<uniform> params : LifeParams;
 
+// The values of the cells being pasted, row by row.
+[[block]]
+struct Cells {
+    cells : array<f32>;
+};
+
+[[group(0), binding(3)]] var<storage> pasteCells : [[access(read)]] Cells;
+
+fn inside(cell: vec2<i32>, rect: vec4<i32>) -> bool {
+    return cell.x >= rect.x && cell.y >= rect.y && cell.x < rect.z && cell.y < rect.w;
+}
+
 fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
     let h : f32 = hsv.x * 6.0f;
     let s : f32 = hsv.y;
@@ -53,8 +101,21 @@ fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
 }
 
 fn render(val: f32) -> vec4<f32> {
-    let thresh : f32 = params.threshold;
+    var thresh : f32 = params.threshold;
+
+    // Lenia cells are continuous, so there's no threshold for being alive.
+    if (params.lenia != 0u32) {
+        thresh = 0.0;
+        if (val <= thresh) {
+            return vec4<f32>(0f, 0f, 0f, 0f);
+        }
+    }
 
+    if (val < 0f) {
+        // Refractory cells of Generations rules fade out in grey-blue.
+        let r: f32 = -val / f32(params.states - 2u32);
+        return vec4<f32>(0.3f * r, 0.3f * r, 0.5f * r, 0.0f);
+    }
     if (val < thresh) {
         return vec4<f32>(0f, 0f, 0f, 0f);
     } else {
@@ -71,5 +132,29 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
         i32(in.tex_coord[1] * f32(params.height))
     );
     var cellValue: f32 = textureLoad(texture, loadCoord).x;
-    return render(cellValue);
+    var color: vec4<f32> = render(cellValue);
+
+    // Cells being pasted are shown, faded a little, in place of the ones
+    // they'll replace; selected cells are tinted blue.
+    if (inside(loadCoord, view.paste)) {
+        let pasteWidth: i32 = view.paste.z - view.paste.x;
+        let i: i32 = (loadCoord.y - view.paste.y) * pasteWidth + (loadCoord.x - view.paste.x);
+        color = mix(render(pasteCells.cells[i]), vec4<f32>(0.5, 0.5, 0.5, 0.0), 0.3);
+    } else {
+        if (inside(loadCoord, view.selection)) {
+            color = mix(color, vec4<f32>(0.2, 0.4, 1.0, 0.0), 0.3);
+        }
+    }
+
+    // Grid lines cover the bottom row and left column of pixels of each cell.
+    if (view.grid_lines != 0u32) {
+        let inCell: vec2<f32> = vec2<f32>(
+            fract(in.tex_coord[0] * f32(params.width)),
+            fract(in.tex_coord[1] * f32(params.height))
+        ) * view.scale;
+        if (inCell.x < 1.0 || inCell.y < 1.0) {
+            color = mix(color, vec4<f32>(0.25, 0.25, 0.25, 0.0), 0.5);
+        }
+    }
+    return color;
 }
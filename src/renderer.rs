@@ -1,4 +1,6 @@
-// This renders a rectangular wgpu::Texture using a pair of triangles.
+// This renders a rectangular wgpu::Texture using a pair of triangles,
+// placed in the window by a Camera, along with any selection and any cells
+// about to be pasted.
 // based on https://github.com/gfx-rs/wgpu-rs/blob/master/examples/cube/main.rs
 
 use bytemuck::{Pod, Zeroable};
@@ -6,7 +8,10 @@ use std::{borrow::Cow, mem};
 use wgpu::util::DeviceExt;
 
 use crate::{
+    camera::Camera,
+    framework::OPENGL_TO_WGPU_MATRIX,
     life_params::LifeParams,
+    selection::{Clip, Rect},
     texture::Texture,
 };
 
@@ -14,10 +19,33 @@ pub struct Renderer {
     vertex_buf: wgpu::Buffer,
     index_buf: wgpu::Buffer,
     index_count: usize,
+    view_buf: wgpu::Buffer,
+    paste_buf: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     pipeline: wgpu::RenderPipeline,
 }
 
+// Cells must be at least this many pixels wide for lines to be drawn
+// between them.
+const MIN_GRID_LINE_SCALE: f32 = 6.0;
+
+// The color of the window around the grid, where it doesn't fill it.
+const LETTERBOX: wgpu::Color = wgpu::Color { r: 0.08, g: 0.08, b: 0.08, a: 1.0 };
+
+// This is shared with the vertex and fragment shaders; see View in
+// render.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct View {
+    transform: [[f32; 4]; 4],
+    scale: f32,
+    grid_lines: u32,
+    _pad: [u32; 2],
+    selection: [i32; 4],
+    paste: [i32; 4],
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct Vertex {
@@ -74,7 +102,25 @@ impl Renderer {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        // Bind the texture and params using a bind group.
+        // Create the uniform buffer for the view, which starts out showing
+        // the texture across the whole window.
+        let view = View {
+            transform: OPENGL_TO_WGPU_MATRIX.into(),
+            scale: 1.0,
+            ..View::zeroed()
+        };
+        let view_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View buffer"),
+            contents: bytemuck::bytes_of(&view),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Create the storage buffer for the cells being pasted, which starts
+        // out with nothing in it.
+        let paste_buf = Renderer::create_paste_buffer(device, &[0.0]);
+
+        // Bind the texture, params, view transform and the cells being
+        // pasted using a bind group.
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
@@ -90,22 +136,31 @@ impl Renderer {
                     ty: params.binding_type(),
                     count: None,
                 },
-            ],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: texture.binding_resource(),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            mem::size_of::<View>() as _),
+                    },
+                    count: None,
                 },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: params.binding_resource(),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
             ],
-            label: None,
         });
+        let bind_group = Renderer::create_bind_group(device, &bind_group_layout,
+            params, texture, &view_buf, &paste_buf);
 
         // Create the render pipeline.
         let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -155,11 +210,102 @@ impl Renderer {
             vertex_buf,
             index_buf,
             index_count: index_data.len(),
+            view_buf,
+            paste_buf,
+            bind_group_layout,
             bind_group,
             pipeline,
         }
     }
 
+    fn create_paste_buffer(
+        device: &wgpu::Device,
+        values: &[f32],
+    ) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Paste buffer"),
+            contents: bytemuck::cast_slice(values),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params: &LifeParams,
+        texture: &Texture,
+        view_buf: &wgpu::Buffer,
+        paste_buf: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: texture.binding_resource(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.binding_resource(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: view_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: paste_buf.as_entire_binding(),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    // Draw from a new texture and parameters, after the grid is resized.
+    pub fn set_sources(
+        &mut self,
+        device: &wgpu::Device,
+        params: &LifeParams,
+        texture: &Texture,
+    ) {
+        self.bind_group = Renderer::create_bind_group(device, &self.bind_group_layout,
+            params, texture, &self.view_buf, &self.paste_buf);
+    }
+
+    // Show the given cells wherever set_view() says they're being pasted.
+    pub fn set_paste(
+        &mut self,
+        device: &wgpu::Device,
+        params: &LifeParams,
+        texture: &Texture,
+        clip: &Clip,
+    ) {
+        self.paste_buf = Renderer::create_paste_buffer(device, clip.values());
+        self.set_sources(device, params, texture);
+    }
+
+    // Show the grid as the camera sees it, from the next render on, with
+    // lines between the cells if they're asked for and there's room, and
+    // with any selection, and any cells being pasted, on top.
+    pub fn set_view(
+        &self,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        grid_lines: bool,
+        selection: Option<Rect>,
+        paste: Option<Rect>,
+    ) {
+        let view = View {
+            transform: (OPENGL_TO_WGPU_MATRIX * camera.transform()).into(),
+            scale: camera.scale(),
+            grid_lines: (grid_lines && camera.scale() >= MIN_GRID_LINE_SCALE) as u32,
+            selection: selection.map_or([0; 4], |rect| rect.corners()),
+            paste: paste.map_or([0; 4], |rect| rect.corners()),
+            ..View::zeroed()
+        };
+        queue.write_buffer(&self.view_buf, 0, bytemuck::bytes_of(&view));
+    }
+
     pub fn render(
         &mut self,
         encoder: &mut wgpu::CommandEncoder,
@@ -171,7 +317,7 @@ impl Renderer {
                 view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    load: wgpu::LoadOp::Clear(LETTERBOX),
                     store: true,
                 },
             }],
@@ -0,0 +1,457 @@
+// Rules for Life-like cellular automata, written in "B3/S23" notation.
+//
+// A rule is described by two sets of neighbor counts: the counts for which
+// a dead cell is born, and the counts for which a live cell survives.
+// Each set is stored as a bitmask, where bit N is set if a cell with N live
+// neighbors is affected; this is the form the compute shader consumes.
+//
+// Generations rules such as "B2/S/C3" (Brian's Brain) add a number of states.
+// A live cell that fails to survive doesn't die immediately; it passes
+// through (states - 2) refractory states, during which it is neither alive
+// nor able to be born, before it finally becomes dead.
+//
+// Larger-than-Life rules such as "R5,C0,M1,S34..58,B34..45,NM" (Bosco's rule)
+// count neighbors out to a larger radius, in either a Moore (square) or
+// von Neumann (diamond) neighborhood. Their neighbor counts can be far too
+// large for a bitmask, so birth and survival are given as ranges instead.
+//
+// Lenia rules such as "Lenia:R13,m0.15,s0.015,dt0.1" describe a continuous
+// automaton, where each cell holds a value between 0 and 1. Neighbors are
+// weighted by a smooth ring-shaped kernel of the given radius, and the
+// weighted average is fed through a Gaussian growth function centered on
+// mu with width sigma, whose output is added to the cell scaled by dt.
+
+use std::{fmt, str::FromStr};
+
+// The largest number of neighbors a cell can have in a Moore neighborhood.
+const MAX_NEIGHBORS: u32 = 8;
+
+// The largest neighborhood radius supported for Larger-than-Life rules.
+pub const MAX_RADIUS: u32 = 10;
+
+// The largest kernel radius supported for Lenia rules.
+pub const MAX_LENIA_RADIUS: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    Life(LifeRule),
+    Lenia(LeniaRule),
+}
+
+impl Rule {
+    // Conway's Game of Life.
+    pub fn conway() -> Self {
+        Rule::Life(LifeRule::conway())
+    }
+
+    // Parse any of the rule notations described above.
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        if rule.len() >= 5 && rule[..5].eq_ignore_ascii_case("lenia") {
+            LeniaRule::parse(rule).map(Rule::Lenia)
+        } else {
+            LifeRule::parse(rule).map(Rule::Life)
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::conway()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::parse(s)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Life(rule) => rule.fmt(f),
+            Rule::Lenia(rule) => rule.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Neighborhood {
+    Moore,
+    VonNeumann,
+}
+
+// A set of neighbor counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Counts {
+    // Bit N is set if N is in the set.
+    Mask(u32),
+    // Every count in the inclusive range is in the set.
+    Range(u32, u32),
+}
+
+impl Counts {
+    // The counts in this set that can be expressed as a bitmask.
+    pub fn mask(&self) -> u32 {
+        match *self {
+            Counts::Mask(mask) => mask,
+            Counts::Range(_, _) => 0,
+        }
+    }
+
+    // The counts in this set that are expressed as a range. An empty set
+    // is returned as a range whose minimum exceeds its maximum.
+    pub fn range(&self) -> (u32, u32) {
+        match *self {
+            Counts::Mask(_) => (1, 0),
+            Counts::Range(lo, hi) => (lo, hi),
+        }
+    }
+
+    pub fn contains(&self, count: u32) -> bool {
+        let (lo, hi) = self.range();
+        (count < 32 && self.mask() & (1 << count) != 0) || (lo <= count && count <= hi)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LifeRule {
+    birth: Counts,
+    survival: Counts,
+    states: u32,
+    radius: u32,
+    neighborhood: Neighborhood,
+    include_center: bool,
+}
+
+impl LifeRule {
+    // Conway's Game of Life.
+    pub fn conway() -> Self {
+        LifeRule {
+            birth: Counts::Mask(1 << 3),
+            survival: Counts::Mask(1 << 2 | 1 << 3),
+            states: 2,
+            radius: 1,
+            neighborhood: Neighborhood::Moore,
+            include_center: false,
+        }
+    }
+
+    // Parse a rule such as "B36/S23", or "B36S23" without the slash. The
+    // "S23/B36" ordering and the older "23/36" survival/birth notation are
+    // accepted too. Generations rules append a state count, as in "B2/S/C3"
+    // or "345/2/4". Larger-than-Life rules use the
+    // "R5,C0,M1,S34..58,B34..45,NM" notation.
+    fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        let mut chars = rule.chars();
+        if matches!(chars.next(), Some('R') | Some('r'))
+            && matches!(chars.next(), Some(c) if c.is_ascii_digit()) {
+            return LifeRule::parse_ltl(rule);
+        }
+
+        // Put back any slashes left out before the letter of a part.
+        let mut slashed = String::new();
+        for c in rule.chars() {
+            if "BSCGbscg".contains(c) && !slashed.is_empty() && !slashed.ends_with('/') {
+                slashed.push('/');
+            }
+            slashed.push(c);
+        }
+        let parts: Vec<&str> = slashed.split('/').collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(format!("rule \"{}\" should have the form B.../S...", rule));
+        }
+
+        let (mut birth, mut survival, mut states) = (None, None, None);
+        for part in &parts {
+            match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') => birth = Some(LifeRule::parse_counts(rule, &part[1..])?),
+                Some('S') => survival = Some(LifeRule::parse_counts(rule, &part[1..])?),
+                Some('C') | Some('G') => states = Some(LifeRule::parse_states(rule, &part[1..])?),
+                _ => {}
+            }
+        }
+
+        let (birth, survival) = match (birth, survival) {
+            (Some(birth), Some(survival)) => (birth, survival),
+            (None, None) => (
+                LifeRule::parse_counts(rule, parts[1])?,
+                LifeRule::parse_counts(rule, parts[0])?,
+            ),
+            _ => return Err(format!("rule \"{}\" needs both a B and an S part", rule)),
+        };
+        let states = match (states, parts.get(2)) {
+            (Some(states), _) => states,
+            (None, Some(part)) => LifeRule::parse_states(rule, part)?,
+            (None, None) => 2,
+        };
+
+        Ok(LifeRule {
+            birth: Counts::Mask(birth),
+            survival: Counts::Mask(survival),
+            states,
+            ..LifeRule::conway()
+        })
+    }
+
+    // Parse a Larger-than-Life rule. Every field but the radius is optional.
+    fn parse_ltl(rule: &str) -> Result<Self, String> {
+        let mut ltl = LifeRule {
+            birth: Counts::Range(1, 0),
+            survival: Counts::Range(1, 0),
+            ..LifeRule::conway()
+        };
+
+        for field in rule.split(',') {
+            let field = field.trim();
+            let value = &field[1.min(field.len())..];
+            let number = |value: &str| value.parse::<u32>().map_err(|_|
+                format!("rule \"{}\" has an invalid field \"{}\"", rule, field));
+
+            match field.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('R') => ltl.radius = number(value)?,
+                Some('C') => ltl.states = number(value)?.max(2),
+                Some('M') => ltl.include_center = number(value)? != 0,
+                Some('S') => ltl.survival = LifeRule::parse_range(rule, value)?,
+                Some('B') => ltl.birth = LifeRule::parse_range(rule, value)?,
+                Some('N') => ltl.neighborhood = match value {
+                    "M" | "m" => Neighborhood::Moore,
+                    "N" | "n" => Neighborhood::VonNeumann,
+                    _ => return Err(format!(
+                        "rule \"{}\" has an unsupported neighborhood \"{}\"", rule, value)),
+                },
+                _ => return Err(format!("rule \"{}\" has an unknown field \"{}\"", rule, field)),
+            }
+        }
+
+        if ltl.radius < 1 || ltl.radius > MAX_RADIUS {
+            return Err(format!("rule \"{}\" has a radius outside 1..{}", rule, MAX_RADIUS));
+        }
+        Ok(ltl)
+    }
+
+    fn parse_range(rule: &str, range: &str) -> Result<Counts, String> {
+        let bounds: Vec<&str> = if range.contains("..") {
+            range.split("..").collect()
+        } else {
+            range.split('-').collect()
+        };
+        match bounds.as_slice() {
+            [lo, hi] => match (lo.parse(), hi.parse()) {
+                (Ok(lo), Ok(hi)) => Ok(Counts::Range(lo, hi)),
+                _ => Err(format!("rule \"{}\" has an invalid range \"{}\"", rule, range)),
+            },
+            _ => Err(format!("rule \"{}\" has an invalid range \"{}\"", rule, range)),
+        }
+    }
+
+    fn parse_states(rule: &str, digits: &str) -> Result<u32, String> {
+        match digits.parse() {
+            Ok(states) if states >= 2 => Ok(states),
+            _ => Err(format!("rule \"{}\" has an invalid state count \"{}\"", rule, digits)),
+        }
+    }
+
+    fn parse_counts(rule: &str, digits: &str) -> Result<u32, String> {
+        let mut mask = 0;
+        for c in digits.chars() {
+            match c.to_digit(10) {
+                Some(n) if n <= MAX_NEIGHBORS => mask |= 1 << n,
+                _ => return Err(format!(
+                    "rule \"{}\" has an invalid neighbor count '{}'", rule, c)),
+            }
+        }
+        Ok(mask)
+    }
+
+    pub fn birth(&self) -> Counts {
+        self.birth
+    }
+
+    pub fn survival(&self) -> Counts {
+        self.survival
+    }
+
+    pub fn states(&self) -> u32 {
+        self.states
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    // Whether a cell counts itself as one of its own neighbors.
+    pub fn include_center(&self) -> bool {
+        self.include_center
+    }
+
+    fn is_ltl(&self) -> bool {
+        self.radius > 1
+            || self.neighborhood != Neighborhood::Moore
+            || self.include_center
+            || matches!(self.birth, Counts::Range(_, _))
+            || matches!(self.survival, Counts::Range(_, _))
+    }
+}
+
+impl fmt::Display for LifeRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ltl() {
+            let range = |counts: Counts| -> String {
+                match counts {
+                    Counts::Range(lo, hi) => format!("{}..{}", lo, hi),
+                    Counts::Mask(mask) => {
+                        let lo = mask.trailing_zeros();
+                        let hi = 31 - mask.leading_zeros().min(31);
+                        format!("{}..{}", lo, hi)
+                    }
+                }
+            };
+            return write!(f, "R{},C{},M{},S{},B{},N{}",
+                self.radius,
+                if self.states > 2 { self.states } else { 0 },
+                self.include_center as u32,
+                range(self.survival),
+                range(self.birth),
+                match self.neighborhood {
+                    Neighborhood::Moore => 'M',
+                    Neighborhood::VonNeumann => 'N',
+                });
+        }
+
+        let counts = |mask: u32| -> String {
+            (0..=MAX_NEIGHBORS)
+                .filter(|n| mask & (1 << n) != 0)
+                .map(|n| std::char::from_digit(n, 10).unwrap())
+                .collect()
+        };
+        write!(f, "B{}/S{}", counts(self.birth.mask()), counts(self.survival.mask()))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeniaRule {
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    dt: f32,
+}
+
+impl LeniaRule {
+    // The rule for Orbium, the best-known Lenia creature.
+    pub fn orbium() -> Self {
+        LeniaRule {
+            radius: 13,
+            mu: 0.15,
+            sigma: 0.015,
+            dt: 0.1,
+        }
+    }
+
+    // Parse a rule such as "Lenia:R13,m0.15,s0.015,dt0.1". Fields that are
+    // left out take their values from orbium(). The time step may also be
+    // given as a number of steps per unit time, as in "T10".
+    fn parse(rule: &str) -> Result<Self, String> {
+        let mut lenia = LeniaRule::orbium();
+        let fields = match rule.find(':') {
+            Some(colon) => &rule[colon + 1..],
+            None => "",
+        };
+
+        for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let split = field.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(field.len());
+            let (name, value) = field.split_at(split);
+            let value: f32 = value.parse().map_err(|_|
+                format!("rule \"{}\" has an invalid field \"{}\"", rule, field))?;
+
+            match name {
+                "R" | "r" => lenia.radius = value as u32,
+                "m" | "mu" => lenia.mu = value,
+                "s" | "sigma" => lenia.sigma = value,
+                "dt" => lenia.dt = value,
+                "T" => lenia.dt = 1.0 / value,
+                _ => return Err(format!("rule \"{}\" has an unknown field \"{}\"", rule, field)),
+            }
+        }
+
+        if lenia.radius < 1 || lenia.radius > MAX_LENIA_RADIUS {
+            return Err(format!("rule \"{}\" has a radius outside 1..{}", rule, MAX_LENIA_RADIUS));
+        }
+        if lenia.sigma <= 0.0 || lenia.dt <= 0.0 {
+            return Err(format!("rule \"{}\" needs a positive sigma and dt", rule));
+        }
+        Ok(lenia)
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    pub fn mu(&self) -> f32 {
+        self.mu
+    }
+
+    pub fn sigma(&self) -> f32 {
+        self.sigma
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+}
+
+impl fmt::Display for LeniaRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lenia:R{},m{},s{},dt{}", self.radius, self.mu, self.sigma, self.dt)
+    }
+}
+
+#[test]
+fn parse_rules() {
+    assert_eq!(Rule::parse("B3/S23").unwrap(), Rule::conway());
+    assert_eq!(LifeRule::parse("s23/b3").unwrap(), LifeRule::conway());
+    assert_eq!(LifeRule::parse("23/3").unwrap(), LifeRule::conway());
+    assert_eq!(Rule::parse("B36/S23").unwrap().to_string(), "B36/S23");
+    assert_eq!(LifeRule::parse("B2/S").unwrap().survival(), Counts::Mask(0));
+    assert_eq!(LifeRule::parse("B2/S/C3").unwrap().states(), 3);
+    assert_eq!(LifeRule::parse("345/2/4").unwrap().to_string(), "B2/S345/C4");
+    assert!(LifeRule::parse("B9/S23").is_err());
+    assert!(LifeRule::parse("B2/S/C1").is_err());
+    assert_eq!(Rule::parse("B3S23").unwrap(), Rule::conway());
+    assert_eq!(LifeRule::parse("s23b3").unwrap(), LifeRule::conway());
+    assert_eq!(LifeRule::parse("B2SC3").unwrap(), LifeRule::parse("B2/S/C3").unwrap());
+    assert!(LifeRule::parse("B3S23S4").is_err());
+
+    let bosco = LifeRule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+    assert_eq!(bosco.radius(), 5);
+    assert!(bosco.include_center());
+    assert!(bosco.survival().contains(58) && !bosco.survival().contains(59));
+    assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+    assert_eq!(LifeRule::parse("R2,B3-5,S2-6,NN").unwrap().neighborhood(),
+        Neighborhood::VonNeumann);
+    assert!(LifeRule::parse("R11,S1..2,B1..2").is_err());
+
+    assert_eq!(Rule::parse("Lenia").unwrap(), Rule::Lenia(LeniaRule::orbium()));
+    match Rule::parse("lenia:R10,m0.2,s0.02,T5").unwrap() {
+        Rule::Lenia(lenia) => {
+            assert_eq!(lenia.radius(), 10);
+            assert_eq!(lenia.dt(), 0.2);
+        }
+        rule => panic!("unexpected rule {}", rule),
+    }
+    assert!(Rule::parse("Lenia:R13,s0").is_err());
+}
@@ -0,0 +1,290 @@
+// Selecting a rectangle of the grid, and moving cells around through a
+// clipboard: cells copied from the grid, which can be turned and flipped
+// before they're pasted back somewhere else. Copied cells are also put on
+// the system clipboard as RLE text, and patterns copied in other programs
+// can be pasted.
+
+use crate::{
+    dimensions::Dimensions,
+    edit::CellEdit,
+    pattern::Pattern,
+};
+
+// A rectangle of cells, from the cell in column x and row y, which may be
+// off the grid, as a Camera's points are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: i64,
+    pub y: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    // The rectangle with the given cells at opposite corners, either way
+    // round.
+    pub fn spanning(
+        (x0, y0): (i64, i64),
+        (x1, y1): (i64, i64),
+    ) -> Self {
+        Rect {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: ((x1 - x0).abs() + 1) as u32,
+            height: ((y1 - y0).abs() + 1) as u32,
+        }
+    }
+
+    // The whole of a grid.
+    pub fn grid(dimensions: Dimensions) -> Self {
+        Rect {
+            x: 0,
+            y: 0,
+            width: dimensions.width(),
+            height: dimensions.height(),
+        }
+    }
+
+    // The cells of the rectangle which are on a grid, row by row.
+    pub fn cells(
+        &self,
+        dimensions: Dimensions,
+    ) -> Vec<(u32, u32)> {
+        let (w, h) = (i64::from(dimensions.width()), i64::from(dimensions.height()));
+        let columns = self.x.max(0)..(self.x + i64::from(self.width)).min(w);
+        let rows = self.y.max(0)..(self.y + i64::from(self.height)).min(h);
+        rows.flat_map(|y| columns.clone().map(move |x| (x as u32, y as u32)))
+            .collect()
+    }
+
+    // The first cell of the rectangle, and the one past its last, as the
+    // renderer takes them.
+    pub fn corners(&self) -> [i32; 4] {
+        [
+            self.x as i32,
+            self.y as i32,
+            (self.x + i64::from(self.width)) as i32,
+            (self.y + i64::from(self.height)) as i32,
+        ]
+    }
+}
+
+// The cell at a point on the grid, as a Camera gives it, moved onto the
+// grid if it's off it.
+pub fn grid_cell(
+    (x, y): (f32, f32),
+    dimensions: Dimensions,
+) -> (i64, i64) {
+    (
+        (x.floor() as i64).max(0).min(i64::from(dimensions.width()) - 1),
+        (y.floor() as i64).max(0).min(i64::from(dimensions.height()) - 1),
+    )
+}
+
+// Cells taken from the grid, or from a pattern, with the value of each,
+// row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Clip {
+    pub fn new(
+        width: u32,
+        height: u32,
+        values: Vec<f32>,
+    ) -> Self {
+        assert_eq!(values.len(), width as usize * height as usize);
+        Clip {
+            width,
+            height,
+            values,
+        }
+    }
+
+    // The live cells of a pattern, which are given a value of 1; the rest
+    // are dead. A clip's rows go up the grid, as the grid's own do, and a
+    // pattern's go down it, so the pattern's first row is the clip's last,
+    // as it is when the pattern is loaded.
+    pub fn from_pattern(pattern: &Pattern) -> Self {
+        let width = pattern.width() as usize;
+        let height = pattern.height() as usize;
+        let mut values = vec![0.0; width * height];
+        for &(x, y) in pattern.cells() {
+            values[(height - 1 - y as usize) * width + x as usize] = 1.0;
+        }
+        Clip::new(pattern.width(), pattern.height(), values)
+    }
+
+    // The cells which are alive, as a pattern the same size as the clip,
+    // with its rows going down, as a pattern's do.
+    pub fn to_pattern(
+        &self,
+        threshold: f32,
+    ) -> Pattern {
+        let width = self.width as usize;
+        let mut cells: Vec<(u32, u32)> = self.values.iter()
+            .enumerate()
+            .filter(|(_, &v)| v > threshold)
+            .map(|(i, _)| ((i % width) as u32, self.height - 1 - (i / width) as u32))
+            .collect();
+        cells.sort_by_key(|&(x, y)| (y, x));
+        Pattern::new(self.width, self.height, cells)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    fn value(
+        &self,
+        x: u32,
+        y: u32,
+    ) -> f32 {
+        self.values[y as usize * self.width as usize + x as usize]
+    }
+
+    // Turn the cells a quarter turn clockwise, as they're shown, with rows
+    // going up the window.
+    pub fn rotate(&self) -> Self {
+        let (w, h) = (self.width, self.height);
+        let values = (0..w)
+            .flat_map(|y| (0..h).map(move |x| (x, y)))
+            .map(|(x, y)| self.value(w - 1 - y, x))
+            .collect();
+        Clip::new(h, w, values)
+    }
+
+    // Flip the cells from left to right.
+    pub fn flip_x(&self) -> Self {
+        let (w, h) = (self.width, self.height);
+        let values = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| self.value(w - 1 - x, y))
+            .collect();
+        Clip::new(w, h, values)
+    }
+
+    // Flip the cells from top to bottom.
+    pub fn flip_y(&self) -> Self {
+        let (w, h) = (self.width, self.height);
+        let values = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| self.value(x, h - 1 - y))
+            .collect();
+        Clip::new(w, h, values)
+    }
+
+    // Where the cells go when they're pasted with their middle at a cell.
+    pub fn rect(
+        &self,
+        (x, y): (i64, i64),
+    ) -> Rect {
+        Rect {
+            x: x - i64::from(self.width / 2),
+            y: y - i64::from(self.height / 2),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // The edits which paste the cells with their middle at a cell, leaving
+    // out any which are off the grid.
+    pub fn paste(
+        &self,
+        at: (i64, i64),
+        dimensions: Dimensions,
+    ) -> Vec<CellEdit> {
+        let rect = self.rect(at);
+        rect.cells(dimensions).into_iter()
+            .map(|(x, y)| {
+                let value = self.value((i64::from(x) - rect.x) as u32,
+                    (i64::from(y) - rect.y) as u32);
+                ((x, y), value)
+            })
+            .collect()
+    }
+}
+
+// The system clipboard, where there is one.
+pub struct SystemClipboard {
+    #[cfg(not(target_arch = "wasm32"))]
+    clipboard: Option<arboard::Clipboard>,
+    // The text last put on the clipboard, or taken from it, so that it's
+    // only read as a pattern once.
+    seen: Option<String>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        SystemClipboard {
+            #[cfg(not(target_arch = "wasm32"))]
+            clipboard: arboard::Clipboard::new().ok(),
+            seen: None,
+        }
+    }
+
+    pub fn set(
+        &mut self,
+        text: String,
+    ) -> Result<(), String> {
+        self.seen = Some(text.clone());
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(clipboard) = &mut self.clipboard {
+            return clipboard.set_text(text).map_err(|e| e.to_string());
+        }
+        Err("there's no system clipboard".to_string())
+    }
+
+    // The text on the clipboard, if it's changed since it was last set or
+    // taken.
+    pub fn take_new(&mut self) -> Option<String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(clipboard) = &mut self.clipboard {
+            let text = clipboard.get_text().ok()?;
+            if self.seen.as_ref() != Some(&text) {
+                self.seen = Some(text.clone());
+                return Some(text);
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn clips() {
+    assert_eq!(Rect::spanning((5, 1), (2, 3)), Rect { x: 2, y: 1, width: 4, height: 3 });
+
+    // A clip two cells wide, with a live cell at its left end: a quarter
+    // turn puts that at the top.
+    let clip = Clip::new(2, 1, vec![1.0, 0.0]);
+    let turned = clip.rotate();
+    assert_eq!(turned, Clip::new(1, 2, vec![0.0, 1.0]));
+    assert_eq!(turned.rotate().rotate().rotate(), clip);
+    assert_eq!(clip.flip_x(), Clip::new(2, 1, vec![0.0, 1.0]));
+    assert_eq!(turned.flip_y(), Clip::new(1, 2, vec![1.0, 0.0]));
+
+    // Patterns go in and out whole, including any dead space around them,
+    // and the clip's bottom row is the pattern's last.
+    let pattern = Clip::new(3, 2, vec![0.0, 0.9, 0.0, 0.2, 0.0, 0.0]).to_pattern(0.5);
+    assert_eq!((pattern.width(), pattern.height()), (3, 2));
+    assert_eq!(pattern.cells(), &[(1, 1)]);
+    assert_eq!(Clip::from_pattern(&pattern), Clip::new(3, 2, vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0]));
+
+    // Pasting in a corner leaves out the cells which are off the grid.
+    let clip = Clip::new(3, 3, (0..9).map(|v| v as f32).collect());
+    assert_eq!(clip.rect((0, 0)), Rect { x: -1, y: -1, width: 3, height: 3 });
+    assert_eq!(clip.paste((0, 0), Dimensions::new(8, 8)),
+        vec![((0, 0), 4.0), ((1, 0), 5.0), ((0, 1), 7.0), ((1, 1), 8.0)]);
+    assert_eq!(grid_cell((-3.5, 9.5), Dimensions::new(8, 8)), (0, 7));
+}
@@ -0,0 +1,257 @@
+// The state of a simulation, apart from anything to do with displaying it:
+// the Life grid and the parameters it was set up with. This is shared by
+// the windowed program and headless runs.
+
+use std::{fs, path::Path};
+
+use rand::{
+    distributions::{Distribution, Uniform},
+    SeedableRng,
+};
+
+use crate::{
+    config::{Config, Resize},
+    dimensions::Dimensions,
+    life::{Life, LifeGrid, Representation},
+    life_params::{LifeParams, Params},
+    pattern::{self, Pattern},
+    rule::Rule,
+    texture::Texture,
+};
+
+// Cells with values above this are alive.
+pub const THRESHOLD: f32 = 0.70;
+
+// How much a surviving cell fades in each generation.
+pub const DECAY: f32 = 0.01;
+
+pub struct Simulation {
+    pub life: Life,
+    pub params: LifeParams,
+    pub texture: Texture,
+    pub dimensions: Dimensions,
+    pub threshold: f32,
+    pub rule: Rule,
+}
+
+impl Simulation {
+    // Set up the grid as the config says: with a pattern or a random soup,
+    // and run for the warm-up generations.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: Dimensions,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let threshold = THRESHOLD;
+        let (rule, cells) = initial_state(dimensions, config)?;
+
+        // Packed cells can't fade, so they are given no decay; that keeps
+        // them in step with the CPU implementation when verifying them.
+        let (representation, decay) = if config.packed {
+            (Representation::Packed, 0.0)
+        } else {
+            (Representation::Float, DECAY)
+        };
+
+        // Parameters for the game, shared between compute and fragment shaders.
+        let params = LifeParams::new(device, dimensions, threshold, decay,
+            &rule, &config.topology);
+        representation.check(params.params())?;
+
+        // Create the texture that's shared between compute and fragment shaders.
+        let texture = Texture::new(device, dimensions, wgpu::TextureFormat::R32Float);
+
+        // Initialize the life algorithm.
+        let mut life = Life::new(device, dimensions, &params, &texture,
+            representation, config.kernel);
+
+        // Set the initial state for all cells in the life grid.
+        life.import(device, queue, cells);
+
+        let mut simulation = Simulation {
+            life,
+            params,
+            texture,
+            dimensions,
+            threshold,
+            rule,
+        };
+
+        // Step the algorithm a few times, so the initial image looks Life-like.
+        simulation.run(device, queue, config.warmup().into());
+        Ok(simulation)
+    }
+
+    // Run the given number of generations, waiting for them to finish.
+    pub fn run(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        generations: u64,
+    ) {
+        self.life.run(&(device, queue), generations);
+    }
+
+    // Change the parameters the grid is stepped with. They're uploaded to
+    // the GPU before the next frame is drawn.
+    pub fn set_params(
+        &mut self,
+        params: Params,
+    ) -> Result<(), String> {
+        self.life.set_params(&params)?;
+        self.threshold = params.threshold;
+        self.params.set(params);
+        Ok(())
+    }
+
+    // Change the size of the grid, moving its cells across as the resize
+    // policy says; any that don't fit are lost. The texture and the
+    // parameters' buffer are made anew, so anything bound to them needs to
+    // be bound to the new ones.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: Dimensions,
+        resize: Resize,
+    ) -> Result<(), String> {
+        let mut params = *self.params.params();
+        params.width = dimensions.width();
+        params.height = dimensions.height();
+        self.life.representation().check(&params)?;
+
+        let old = self.life.export(device, queue);
+        let cells = copy_grid(&old, self.dimensions, dimensions,
+            resize.offset(self.dimensions, dimensions));
+
+        self.params.resize(device, dimensions);
+        self.texture = Texture::new(device, dimensions, wgpu::TextureFormat::R32Float);
+        self.life.resize(device, queue, &self.params, &self.texture, cells);
+        self.dimensions = dimensions;
+        Ok(())
+    }
+
+    // Save the grid to a file; see save_grid().
+    pub fn save(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+    ) -> Result<(), String> {
+        let grid = self.life.export(device, queue);
+        save_grid(path, &grid, self.dimensions, self.threshold, &self.rule)
+    }
+}
+
+// The rule and the initial grid that the config asks for: a pattern, or a
+// random soup.
+pub fn initial_state(
+    dimensions: Dimensions,
+    config: &Config,
+) -> Result<(Rule, Vec<f32>), String> {
+    // Load the pattern first, since it may say which rule it's for.
+    let pattern = match &config.pattern {
+        Some(path) => Some(pattern::load(path, dimensions)?),
+        None => None,
+    };
+    let rule = match (config.rule, pattern.as_ref().and_then(Pattern::rule)) {
+        (Some(rule), _) => rule,
+        (None, Some(rule)) => Rule::parse(rule).unwrap_or_else(|e| {
+            println!("Using B3/S23, since {}", e);
+            Rule::conway()
+        }),
+        (None, None) => Rule::conway(),
+    };
+
+    let cells = match &pattern {
+        Some(pattern) => pattern.to_grid(dimensions, pattern.centered_in(dimensions))?,
+        None => soup(dimensions, config.seed, config.density, THRESHOLD),
+    };
+    Ok((rule, cells))
+}
+
+// Save a grid to a file: as a greyscale PGM image if the path ends in
+// ".pgm", or otherwise as a pattern in the format given by the extension.
+pub fn save_grid(
+    path: &Path,
+    grid: &[f32],
+    dimensions: Dimensions,
+    threshold: f32,
+    rule: &Rule,
+) -> Result<(), String> {
+    if !matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("pgm")) {
+        let mut pattern = Pattern::from_grid(grid, dimensions, threshold);
+        pattern.set_rule(Some(rule.to_string()));
+        return pattern::save(path, &pattern);
+    }
+
+    // Live cells are white, and dead cells black. Lenia cells have no
+    // threshold, so their values are shown directly. The image starts with
+    // the top row, as the grid is shown, which is the grid's last.
+    let lenia = matches!(rule, Rule::Lenia(_));
+    let mut image = format!("P5\n{} {}\n255\n",
+        dimensions.width(), dimensions.height()).into_bytes();
+    image.extend(grid.chunks(dimensions.width() as usize).rev().flatten().map(|&v| {
+        if lenia {
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        } else if v > threshold {
+            255
+        } else {
+            0
+        }
+    }));
+    fs::write(path, image).map_err(|e|
+        format!("couldn't write {}: {}", path.display(), e))
+}
+
+// Copy the cells of a grid into an empty grid of another size, with the
+// top left cell at the given position, which may be outside it.
+fn copy_grid(
+    cells: &[f32],
+    from: Dimensions,
+    to: Dimensions,
+    (left, top): (i64, i64),
+) -> Vec<f32> {
+    let mut grid = vec![0.0; to.area()];
+    for y in 0..i64::from(from.height()) {
+        let ty = y + top;
+        if ty < 0 || ty >= i64::from(to.height()) {
+            continue;
+        }
+        for x in 0..i64::from(from.width()) {
+            let tx = x + left;
+            if tx < 0 || tx >= i64::from(to.width()) {
+                continue;
+            }
+            grid[(ty * i64::from(to.width()) + tx) as usize] =
+                cells[(y * i64::from(from.width()) + x) as usize];
+        }
+    }
+    grid
+}
+
+// Make a random soup of cells, where each cell is alive with the given
+// probability. Live and dead cells take values spread evenly above and
+// below the threshold, so a density of (1 - threshold) gives a plain
+// uniform distribution of values.
+pub fn soup(
+    dimensions: Dimensions,
+    seed: u64,
+    density: f32,
+    threshold: f32,
+) -> Vec<f32> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let unif = Uniform::new_inclusive(0.0, 1.0);
+    let dead = 1.0 - density;
+    (0..dimensions.area()).map(|_| {
+        let v: f32 = unif.sample(&mut rng);
+        if v > dead {
+            threshold + (v - dead) / density * (1.0 - threshold)
+        } else if dead > 0.0 {
+            v / dead * threshold
+        } else {
+            v
+        }
+    }).collect()
+}
@@ -0,0 +1,521 @@
+// Life on an unbounded plane, held as a sparse set of square tiles which
+// are each stepped on the GPU; see life_sparse.wgsl.
+//
+// The tiles live in a fixed number of slots in the cell buffers. Every
+// TILE_MARGIN generations, the GPU works out which tiles have any cells
+// that aren't dead, and which have such cells within TILE_MARGIN cells of
+// their edges. Those are kept, along with any neighbors which the cells
+// near their edges could reach before the next check, and the rest are
+// freed. Since nothing travels faster than one cell per generation under
+// a rule of radius 1, the cells in tiles which aren't held really are all
+// dead, so patterns can travel forever without wrapping into themselves.
+//
+// This only works for Life-like rules of radius 1 under which a cell with
+// no live neighbors stays dead; that is, without B0.
+
+use std::{borrow::Cow, collections::HashMap, mem};
+
+use crate::{
+    cpu_life::in_set,
+    debug_buffer::DebugBuffer,
+    dimensions::Dimensions,
+    directions::{RenderDir, RenderMotion, RenderSources},
+    life::{shader_source, WORKGROUP_SIZE},
+    life_params::{LifeParams, Params},
+    texture::Texture,
+};
+
+// The width and height of a tile, in cells.
+const TILE_SIZE: u32 = 64;
+
+// How often the tiles are checked, in generations, and so how close to an
+// edge a cell has to be for the tile beyond it to be needed.
+const TILE_MARGIN: u32 = 16;
+
+const TILE_AREA: usize = (TILE_SIZE * TILE_SIZE) as usize;
+
+pub struct SparseLife {
+    compute_pipeline: wgpu::ComputePipeline,
+    summary_pipeline: wgpu::ComputePipeline,
+    show_pipeline: wgpu::ComputePipeline,
+    bind_groups: RenderMotion<wgpu::BindGroup>,
+    cell_buffers: RenderSources<wgpu::Buffer>,
+    tile_buffer: wgpu::Buffer,
+    flag_buffer: wgpu::Buffer,
+    view_buffer: wgpu::Buffer,
+    dimensions: Dimensions,
+    threshold: f32,
+    // The slot holding each tile, by the tile's position in tiles.
+    tiles: HashMap<(i64, i64), u32>,
+    // Slots below high_water which aren't in use.
+    free: Vec<u32>,
+    // How many slots have ever been used, which is how many are stepped.
+    high_water: u32,
+    capacity: u32,
+    warned_full: bool,
+    // The position of the texture's top left cell on the plane.
+    view: (i64, i64),
+    frame_num: usize,
+}
+
+impl SparseLife {
+    // Check that cells with these parameters can live on an unbounded plane.
+    pub fn check(params: &Params) -> Result<(), String> {
+        if params.lenia != 0 || params.radius != 1 {
+            return Err("an unbounded plane only works for Life-like rules of radius 1".to_string());
+        }
+        if in_set(0, params.birth, params.birth_min, params.birth_max) {
+            return Err("an unbounded plane doesn't work for rules with B0".to_string());
+        }
+        Ok(())
+    }
+
+    // Take on new parameters, if they can be used on an unbounded plane. The
+    // GPU shares them with the Simulation, which uploads them.
+    pub fn set_params(
+        &mut self,
+        params: &Params,
+    ) -> Result<(), String> {
+        SparseLife::check(params)?;
+        self.threshold = params.threshold;
+        Ok(())
+    }
+
+    // Set up a plane which can hold up to the given number of tiles, and
+    // which is shown in the texture.
+    pub fn new(
+        device: &wgpu::Device,
+        params: &LifeParams,
+        texture: &Texture,
+        capacity: u32,
+    ) -> Result<Self, String> {
+        SparseLife::check(params.params())?;
+        let dimensions = Dimensions::new(params.params().width, params.params().height);
+
+        let source = shader_source(include_str!("life_sparse.wgsl"))
+            .replace("$TILE_SIZE", &TILE_SIZE.to_string())
+            .replace("$TILE_MARGIN", &TILE_MARGIN.to_string());
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("sparse life shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+        });
+
+        let storage = |label, size: usize, usage|
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                usage: wgpu::BufferUsages::STORAGE | usage,
+                size: size as _,
+                mapped_at_creation: false,
+            });
+        // The cells of every tile are bound as one buffer, which the GPU
+        // limits in size.
+        let cell_bufsize = capacity as usize * TILE_AREA * mem::size_of::<f32>();
+        let largest = device.limits().max_storage_buffer_binding_size as usize;
+        if cell_bufsize > largest {
+            return Err(format!("{} tiles won't fit on this GPU; at most {} will",
+                capacity, largest / (TILE_AREA * mem::size_of::<f32>())));
+        }
+        let cell_buffers = RenderSources::new(|_|
+            storage("Sparse cells", cell_bufsize,
+                wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST));
+        let tile_buffer = storage("Tile table",
+            capacity as usize * 9 * mem::size_of::<i32>(), wgpu::BufferUsages::COPY_DST);
+        let flag_buffer = storage("Tile flags",
+            capacity as usize * mem::size_of::<u32>(), wgpu::BufferUsages::COPY_SRC);
+        let view_buffer = storage("View table",
+            view_len(dimensions) * mem::size_of::<i32>(), wgpu::BufferUsages::COPY_DST);
+
+        let buffer_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: params.binding_type(),
+                        count: None,
+                    },
+                    buffer_entry(1, true),
+                    buffer_entry(2, false),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: texture.binding_type(wgpu::StorageTextureAccess::WriteOnly),
+                        count: None,
+                    },
+                    buffer_entry(4, true),
+                    buffer_entry(5, false),
+                    buffer_entry(6, true),
+                ],
+                label: None,
+            });
+        let pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("sparse life pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |label, entry_point|
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+            });
+
+        let bind_groups = RenderMotion::new(|dir|
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.binding_resource(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: cell_buffers.src(dir).as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: cell_buffers.dst(dir).as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: texture.binding_resource(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: tile_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: flag_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: view_buffer.as_entire_binding(),
+                    },
+                ],
+                label: None,
+            })
+        );
+
+        Ok(SparseLife {
+            compute_pipeline: pipeline("sparse life pipeline", "life"),
+            summary_pipeline: pipeline("sparse life summary pipeline", "summarize"),
+            show_pipeline: pipeline("sparse life show pipeline", "show"),
+            bind_groups,
+            cell_buffers,
+            tile_buffer,
+            flag_buffer,
+            view_buffer,
+            dimensions,
+            threshold: params.params().threshold,
+            tiles: HashMap::new(),
+            free: Vec::new(),
+            high_water: 0,
+            capacity,
+            warned_full: false,
+            view: (0, 0),
+            frame_num: 0,
+        })
+    }
+
+    // Import some data onto the plane, with its top left cell at (0, 0),
+    // replacing everything on it. This takes the same grid as Life::import().
+    pub fn import(
+        &mut self,
+        queue: &wgpu::Queue,
+        cells: Vec<f32>,
+        dimensions: Dimensions,
+    ) {
+        assert_eq!(cells.len(), dimensions.area());
+        self.tiles.clear();
+        self.free.clear();
+        self.high_water = 0;
+        self.view = (0, 0);
+
+        let (w, h) = (dimensions.width(), dimensions.height());
+        for ty in 0..(h + TILE_SIZE - 1) / TILE_SIZE {
+            for tx in 0..(w + TILE_SIZE - 1) / TILE_SIZE {
+                // Dead cells are stored as zero, so that any other value
+                // means the tile is in use.
+                let mut tile = vec![0.0f32; TILE_AREA];
+                for y in 0..TILE_SIZE.min(h - ty * TILE_SIZE) {
+                    for x in 0..TILE_SIZE.min(w - tx * TILE_SIZE) {
+                        let v = cells[((ty * TILE_SIZE + y) * w + tx * TILE_SIZE + x) as usize];
+                        if v > self.threshold || v < 0.0 {
+                            tile[(y * TILE_SIZE + x) as usize] = v;
+                        }
+                    }
+                }
+                if tile.iter().all(|&v| v == 0.0) {
+                    continue;
+                }
+                if let Some(slot) = self.allocate((tx as i64, ty as i64)) {
+                    queue.write_buffer(self.src_buf(), slot_offset(slot),
+                        bytemuck::cast_slice(&tile));
+                }
+            }
+        }
+        self.upload_tables(queue);
+    }
+
+    // Export the cells that the texture shows.
+    pub fn export(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<f32> {
+        let export_buf = DebugBuffer::<f32>::new(device, self.capacity as usize * TILE_AREA);
+        export_buf.copyin(device, queue, self.src_buf());
+        let cells = export_buf.read(device).expect("failed to read back the tiles");
+
+        let (w, h) = (self.dimensions.width() as i64, self.dimensions.height() as i64);
+        let mut grid = vec![0.0; self.dimensions.area()];
+        for (&(tx, ty), &slot) in &self.tiles {
+            let tile = &cells[slot as usize * TILE_AREA..][..TILE_AREA];
+            for y in 0..TILE_SIZE as i64 {
+                for x in 0..TILE_SIZE as i64 {
+                    let gx = tx * TILE_SIZE as i64 + x - self.view.0;
+                    let gy = ty * TILE_SIZE as i64 + y - self.view.1;
+                    if gx >= 0 && gx < w && gy >= 0 && gy < h {
+                        grid[(gy * w + gx) as usize] = tile[(y * TILE_SIZE as i64 + x) as usize];
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    // Run the given number of generations, checking the tiles as needed.
+    pub fn run(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        generations: u64,
+    ) {
+        let mut remaining = generations;
+        while remaining > 0 {
+            if self.frame_num % TILE_MARGIN as usize == 0 {
+                self.update_tiles(device, queue);
+            }
+            let until_check = TILE_MARGIN as u64 - (self.frame_num % TILE_MARGIN as usize) as u64;
+            let steps = remaining.min(until_check);
+
+            let mut command_encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: None
+                });
+            for _ in 0..steps {
+                self.step(&mut command_encoder);
+            }
+            queue.submit(Some(command_encoder.finish()));
+            device.poll(wgpu::Maintain::Wait);
+            remaining -= steps;
+        }
+    }
+
+    fn step(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if self.high_water > 0 {
+            let mut cpass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Sparse life step")
+                });
+            cpass.set_bind_group(0, &self.bind_groups.get(RenderDir::dir(self.frame_num)), &[]);
+            cpass.set_pipeline(&self.compute_pipeline);
+            cpass.dispatch(TILE_SIZE / WORKGROUP_SIZE.0, TILE_SIZE / WORKGROUP_SIZE.1,
+                self.high_water);
+        }
+        self.frame_num += 1;
+    }
+
+    // Bring the texture up to date with the cells, before rendering it.
+    pub fn show(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let mut cpass =
+            command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Sparse life show")
+            });
+        let xgroups = (self.dimensions.width() + WORKGROUP_SIZE.0 - 1) / WORKGROUP_SIZE.0;
+        let ygroups = (self.dimensions.height() + WORKGROUP_SIZE.1 - 1) / WORKGROUP_SIZE.1;
+        cpass.set_bind_group(0, &self.bind_groups.get(RenderDir::dir(self.frame_num)), &[]);
+        cpass.set_pipeline(&self.show_pipeline);
+        cpass.dispatch(xgroups, ygroups, 1);
+    }
+
+    // Show the part of the plane whose top left cell is at (left, top).
+    pub fn set_view(
+        &mut self,
+        queue: &wgpu::Queue,
+        left: i64,
+        top: i64,
+    ) {
+        if self.view == (left, top) {
+            return;
+        }
+        self.view = (left, top);
+        self.upload_tables(queue);
+    }
+
+    pub fn frame_num(&self) -> usize {
+        self.frame_num
+    }
+
+    // How many tiles are in use.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    fn src_buf(&self) -> &wgpu::Buffer {
+        self.cell_buffers.src(RenderDir::dir(self.frame_num))
+    }
+
+    // Keep the tiles which are needed, free the rest, and allocate any
+    // needed tiles which aren't held yet.
+    fn update_tiles(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        if self.high_water == 0 {
+            return;
+        }
+
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("summarizing tiles")
+            });
+        {
+            let mut cpass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Sparse life summary")
+                });
+            cpass.set_bind_group(0, &self.bind_groups.get(RenderDir::dir(self.frame_num)), &[]);
+            cpass.set_pipeline(&self.summary_pipeline);
+            cpass.dispatch(self.high_water, 1, 1);
+        }
+        queue.submit(Some(command_encoder.finish()));
+
+        let flag_buf = DebugBuffer::<u32>::new(device, self.capacity as usize);
+        flag_buf.copyin(device, queue, &self.flag_buffer);
+        let flags = flag_buf.read(device).expect("failed to read back the tile flags");
+
+        let mut needed = Vec::new();
+        for (&(tx, ty), &slot) in &self.tiles {
+            let needs = flags[slot as usize];
+            for n in 0..9 {
+                if needs & (1 << n) != 0 {
+                    needed.push((tx + n % 3 - 1, ty + n / 3 - 1));
+                }
+            }
+        }
+        needed.sort_unstable();
+        needed.dedup();
+
+        let unneeded: Vec<_> = self.tiles.keys()
+            .filter(|tile| needed.binary_search(tile).is_err())
+            .copied()
+            .collect();
+        for tile in unneeded {
+            let slot = self.tiles.remove(&tile).unwrap();
+            self.free.push(slot);
+        }
+        for tile in needed {
+            if !self.tiles.contains_key(&tile) {
+                if let Some(slot) = self.allocate(tile) {
+                    queue.write_buffer(self.src_buf(), slot_offset(slot),
+                        bytemuck::cast_slice(&[0.0f32; TILE_AREA]));
+                }
+            }
+        }
+        self.upload_tables(queue);
+    }
+
+    // Find a slot for a tile, if there's one left.
+    fn allocate(
+        &mut self,
+        tile: (i64, i64),
+    ) -> Option<u32> {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None if self.high_water < self.capacity => {
+                self.high_water += 1;
+                self.high_water - 1
+            }
+            None => {
+                if !self.warned_full {
+                    println!("The plane has outgrown its {} tiles, so cells beyond them \
+                        will be lost; try a larger --tiles", self.capacity);
+                    self.warned_full = true;
+                }
+                return None;
+            }
+        };
+        self.tiles.insert(tile, slot);
+        Some(slot)
+    }
+
+    // Upload the tile table, and the table of tiles that the texture shows.
+    fn upload_tables(
+        &self,
+        queue: &wgpu::Queue,
+    ) {
+        let mut table = vec![-1i32; self.capacity as usize * 9];
+        for (&(tx, ty), &slot) in &self.tiles {
+            for n in 0..9 {
+                let neighbor = (tx + n % 3 - 1, ty + n / 3 - 1);
+                if let Some(&s) = self.tiles.get(&neighbor) {
+                    table[slot as usize * 9 + n as usize] = s as i32;
+                }
+            }
+        }
+        queue.write_buffer(&self.tile_buffer, 0, bytemuck::cast_slice(&table));
+
+        let size = TILE_SIZE as i64;
+        let (cols, rows) = view_tiles(self.dimensions);
+        let (left, top) = (self.view.0.div_euclid(size), self.view.1.div_euclid(size));
+        let mut view = vec![
+            self.view.0.rem_euclid(size) as i32,
+            self.view.1.rem_euclid(size) as i32,
+            cols as i32,
+            rows as i32,
+        ];
+        for row in 0..rows as i64 {
+            for col in 0..cols as i64 {
+                view.push(self.tiles.get(&(left + col, top + row)).map_or(-1, |&s| s as i32));
+            }
+        }
+        queue.write_buffer(&self.view_buffer, 0, bytemuck::cast_slice(&view));
+    }
+}
+
+// The offset of a slot in the cell buffers.
+fn slot_offset(slot: u32) -> wgpu::BufferAddress {
+    (slot as usize * TILE_AREA * mem::size_of::<f32>()) as _
+}
+
+// The number of columns and rows of tiles that a texture can overlap.
+fn view_tiles(dimensions: Dimensions) -> (u32, u32) {
+    (dimensions.width() / TILE_SIZE + 2, dimensions.height() / TILE_SIZE + 2)
+}
+
+// The length of the view table for a texture.
+fn view_len(dimensions: Dimensions) -> usize {
+    let (cols, rows) = view_tiles(dimensions);
+    4 + (cols * rows) as usize
+}
@@ -1,9 +1,13 @@
+use std::num::NonZeroU32;
+
 use crate::{
     dimensions::Dimensions,
 };
 
 pub struct Texture {
+    texture: wgpu::Texture,
     texture_view: wgpu::TextureView,
+    dimensions: Dimensions,
     format: wgpu::TextureFormat,
 }
 
@@ -24,16 +28,50 @@ impl Texture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::SAMPLED | wgpu::TextureUsages::STORAGE,
+            usage: wgpu::TextureUsages::SAMPLED
+                 | wgpu::TextureUsages::STORAGE
+                 | wgpu::TextureUsages::COPY_DST,
         });
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         Texture {
+            texture,
             texture_view,
+            dimensions,
             format,
         }
     }
 
+    // Fill the texture from the CPU, for cells which weren't computed by
+    // the compute shader. This takes one f32 per texel, so the texture
+    // must have a single 32-bit channel.
+    pub fn upload(
+        &self,
+        queue: &wgpu::Queue,
+        texels: &[f32],
+    ) {
+        assert_eq!(texels.len(), self.dimensions.area());
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(self.dimensions.width() * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: self.dimensions.width(),
+                height: self.dimensions.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
     pub fn binding_resource(&self) -> wgpu::BindingResource {
         wgpu::BindingResource::TextureView(&self.texture_view)
     }
@@ -0,0 +1,223 @@
+// The topology of the grid: what lies beyond its edges.
+//
+// Topologies are written in Golly's notation, optionally followed by the
+// size of the grid:
+//
+//   T100,80    a torus, where opposite edges are joined (the default)
+//   T100+5,80  a torus whose top and bottom edges are joined with a shift,
+//              so that a cell leaving the bottom edge reenters the top edge
+//              5 cells further right
+//   P100,80    a bounded plane, where every cell beyond the edges is dead
+//   K100*,80   a Klein bottle, where the edges marked with the asterisk are
+//              joined with a twist; here, a cell leaving the bottom edge
+//              reenters the top edge mirrored from left to right
+//   C100,80    a cross-surface, where both pairs of edges are twisted
+//
+// Golly has no notation for mirrored edges, where the cells beyond an edge
+// reflect those just inside it; they are written as "M100,80" here.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    dimensions::Dimensions,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Torus,
+    Plane,
+    Mirror,
+    KleinBottle,
+    CrossSurface,
+}
+
+// How the shaders handle cells beyond the edges; see boundary().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    Wrap = 0,
+    Dead = 1,
+    Mirror = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Topology {
+    kind: Kind,
+    size: Option<Dimensions>,
+    // Whether a cell crossing the top or bottom edge is mirrored in x, and
+    // whether one crossing the left or right edge is mirrored in y.
+    flip_x: bool,
+    flip_y: bool,
+    // How far a cell crossing the top or bottom edge moves in x, and how
+    // far one crossing the left or right edge moves in y.
+    shift_x: i32,
+    shift_y: i32,
+}
+
+impl Topology {
+    pub fn torus() -> Self {
+        Topology {
+            kind: Kind::Torus,
+            size: None,
+            flip_x: false,
+            flip_y: false,
+            shift_x: 0,
+            shift_y: 0,
+        }
+    }
+
+    pub fn parse(topology: &str) -> Result<Self, String> {
+        let topology = topology.trim();
+        let err = |what: &str| format!("topology \"{}\" {}", topology, what);
+
+        let mut chars = topology.chars();
+        let kind = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('T') => Kind::Torus,
+            Some('P') => Kind::Plane,
+            Some('M') => Kind::Mirror,
+            Some('K') => Kind::KleinBottle,
+            Some('C') => Kind::CrossSurface,
+            _ => return Err(err("should start with one of T, P, M, K or C")),
+        };
+        let mut parsed = Topology { kind, ..Topology::torus() };
+
+        let size = chars.as_str();
+        if size.is_empty() {
+            if kind == Kind::KleinBottle {
+                return Err(err("needs a size, to say which edges are twisted"));
+            }
+        } else {
+            let edges: Vec<&str> = size.split(',').collect();
+            if edges.len() != 2 {
+                return Err(err("should give a size as WIDTH,HEIGHT"));
+            }
+            let (width, twist_x, shift_x) = Topology::parse_edge(edges[0]).ok_or_else(||
+                err("has an invalid width"))?;
+            let (height, twist_y, shift_y) = Topology::parse_edge(edges[1]).ok_or_else(||
+                err("has an invalid height"))?;
+
+            parsed.size = Some(Dimensions::new(width, height));
+            parsed.shift_x = shift_x;
+            parsed.shift_y = shift_y;
+            match kind {
+                Kind::KleinBottle if twist_x != twist_y => {
+                    parsed.flip_x = twist_x;
+                    parsed.flip_y = twist_y;
+                }
+                Kind::KleinBottle => {
+                    return Err(err("needs an asterisk on exactly one edge"));
+                }
+                _ if twist_x || twist_y => {
+                    return Err(err("can only have twisted edges if it is a Klein bottle"));
+                }
+                _ => {}
+            }
+            if (shift_x != 0 || shift_y != 0)
+                && kind != Kind::Torus && kind != Kind::KleinBottle {
+                return Err(err("can only have shifted edges if it is a torus or Klein bottle"));
+            }
+        }
+
+        if kind == Kind::CrossSurface {
+            parsed.flip_x = true;
+            parsed.flip_y = true;
+        }
+        Ok(parsed)
+    }
+
+    // Parse one dimension of the size, such as "100", "100*" or "100+5".
+    fn parse_edge(edge: &str) -> Option<(u32, bool, i32)> {
+        let digits = edge.find(|c: char| !c.is_ascii_digit()).unwrap_or(edge.len());
+        let size = edge[..digits].parse().ok()?;
+        let mut rest = &edge[digits..];
+        let twisted = rest.starts_with('*');
+        if twisted {
+            rest = &rest[1..];
+        }
+        let shift = match rest.chars().next() {
+            None => 0,
+            Some('+') => rest[1..].parse().ok()?,
+            Some('-') => rest.parse().ok()?,
+            _ => return None,
+        };
+        Some((size, twisted, shift))
+    }
+
+    // The size of the grid, if the topology specified one.
+    pub fn size(&self) -> Option<Dimensions> {
+        self.size
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        match self.kind {
+            Kind::Plane => Boundary::Dead,
+            Kind::Mirror => Boundary::Mirror,
+            _ => Boundary::Wrap,
+        }
+    }
+
+    pub fn flips(&self) -> (bool, bool) {
+        (self.flip_x, self.flip_y)
+    }
+
+    pub fn shifts(&self) -> (i32, i32) {
+        (self.shift_x, self.shift_y)
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::torus()
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Topology::parse(s)
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let letter = match self.kind {
+            Kind::Torus => 'T',
+            Kind::Plane => 'P',
+            Kind::Mirror => 'M',
+            Kind::KleinBottle => 'K',
+            Kind::CrossSurface => 'C',
+        };
+        write!(f, "{}", letter)?;
+
+        if let Some(size) = self.size {
+            let twisted = self.kind == Kind::KleinBottle;
+            let edge = |f: &mut fmt::Formatter, len: u32, flip: bool, shift: i32| {
+                write!(f, "{}", len)?;
+                if twisted && flip {
+                    write!(f, "*")?;
+                }
+                if shift != 0 {
+                    write!(f, "{:+}", shift)?;
+                }
+                Ok(())
+            };
+            edge(f, size.width(), self.flip_x, self.shift_x)?;
+            write!(f, ",")?;
+            edge(f, size.height(), self.flip_y, self.shift_y)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_topologies() {
+    assert_eq!(Topology::parse("T").unwrap(), Topology::torus());
+    for notation in &["T100,80", "T100+5,80", "P64,64", "K100*,80", "K100,80*-2", "C30,20"] {
+        assert_eq!(Topology::parse(notation).unwrap().to_string(), *notation);
+    }
+    assert_eq!(Topology::parse("K100*,80").unwrap().flips(), (true, false));
+    assert_eq!(Topology::parse("C30,20").unwrap().flips(), (true, true));
+    assert!(Topology::parse("K100,80").is_err());
+    assert!(Topology::parse("P100+1,80").is_err());
+    assert!(Topology::parse("X100,80").is_err());
+}